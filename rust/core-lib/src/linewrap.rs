@@ -1048,7 +1048,7 @@ mod tests {
     #[test]
     fn expected_soft_breaks() {
         let text = "a b c d ".into();
-        let mut text_cursor = Cursor::new(&text, text.len());
+        let text_cursor = Cursor::new(&text, text.len());
         assert!(!text_cursor.is_boundary::<LinesMetric>());
 
         let Lines { breaks, .. } = make_lines(&text, 1.);
@@ -20,6 +20,7 @@ use crate::metrics::{
     count_breaks_up_to, find_next_break, find_prev_break, is_break_boundary, nth_break_offset,
     BreaksBaseMetric,
 };
+use crate::rope::{LinesMetric, Rope};
 use crate::tree::{DefaultMetricProvider, Leaf, Metric, Node, NodeInfo, TreeBuilder};
 use std::cmp::min;
 use std::mem;
@@ -43,7 +44,7 @@ pub struct BreaksLeaf {
 }
 
 /// The number of breaks.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BreaksInfo(usize);
 
 impl Leaf for BreaksLeaf {
@@ -179,12 +180,63 @@ impl Breaks {
         if range.start >= range.end {
             return 0;
         }
-        let start = self.count_breaks_up_to(range.start);
-        let end = self.count_breaks_up_to(range.end);
-        end.saturating_sub(start)
+        self.measure_range::<BreaksMetric>(Interval::new(range.start, range.end))
     }
 }
 
+/// Returns the byte offset at which visual row `row` starts, when `rope` is
+/// wrapped at its hard newlines and at the soft breaks in `breaks`.
+///
+/// Rows are numbered from 0, and each hard or soft break starts a new row.
+/// Rows past the last break map to `rope.len()`.
+///
+/// Time complexity: O(log² n).
+pub fn visual_row_to_byte(rope: &Rope, breaks: &Breaks, row: usize) -> usize {
+    debug_assert_eq!(rope.len(), breaks.len());
+    let n_hard = rope.measure::<LinesMetric>();
+    let n_soft = breaks.measure::<BreaksMetric>();
+    if row == 0 {
+        return 0;
+    }
+    if row > n_hard + n_soft {
+        return rope.len();
+    }
+    let hard = |i: usize| rope.offset_of_line(i);
+    let soft = |i: usize| breaks.offset_of_break(i);
+
+    // Find the number of hard breaks among the first `row` breaks: the smallest
+    // `h` such that the next hard break falls after the last soft break taken.
+    let mut lo = row.saturating_sub(n_soft);
+    let mut hi = min(row, n_hard);
+    while lo < hi {
+        let h = lo + (hi - lo) / 2;
+        if hard(h + 1) > soft(row - h) {
+            hi = h;
+        } else {
+            lo = h + 1;
+        }
+    }
+    hard(lo).max(soft(row - lo))
+}
+
+/// Returns the index of the hard line of `rope` that contains the soft break
+/// numbered `break_index` (from 0) in `breaks`.
+///
+/// The break's offset is the start of the visual row it begins, so a break
+/// is attributed to the hard line that row is part of.
+///
+/// Time complexity: O(log n).
+///
+/// # Panics
+///
+/// Panics if `break_index` is not less than the number of breaks.
+pub fn hard_line_of_break(rope: &Rope, breaks: &Breaks, break_index: usize) -> usize {
+    debug_assert_eq!(rope.len(), breaks.len());
+    let n_soft = breaks.measure::<BreaksMetric>();
+    assert!(break_index < n_soft, "break index {} out of range ({} breaks)", break_index, n_soft);
+    rope.line_of_offset(breaks.offset_of_break(break_index + 1))
+}
+
 pub struct BreakBuilder {
     b: TreeBuilder<BreaksInfo, BreaksLeaf>,
     leaf: BreaksLeaf,
@@ -217,6 +269,30 @@ impl BreakBuilder {
         self.leaf.len += len;
     }
 
+    /// Adds breaks at each of the absolute `offsets`, followed by a no-break
+    /// tail so that the builder covers `total_len` base units.
+    ///
+    /// Offsets equal to the previous break (or to `0`) are skipped, since a
+    /// zero-length break would not add a new boundary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `offsets` is not sorted or any offset is greater than `total_len`.
+    pub fn extend_from_offsets(&mut self, total_len: usize, offsets: &[usize]) {
+        let mut prev = 0;
+        for &offset in offsets {
+            assert!(offset >= prev, "break offsets must be sorted");
+            assert!(offset <= total_len, "break offset {} exceeds length {}", offset, total_len);
+            if offset > prev {
+                self.add_break(offset - prev);
+                prev = offset;
+            }
+        }
+        if prev < total_len {
+            self.add_no_break(total_len - prev);
+        }
+    }
+
     pub fn build(mut self) -> Breaks {
         self.b.push(Node::<BreaksInfo, BreaksLeaf>::from_leaf(self.leaf));
         self.b.build()
@@ -225,8 +301,12 @@ impl BreakBuilder {
 
 #[cfg(test)]
 mod tests {
-    use crate::breaks::{BreakBuilder, Breaks, BreaksInfo, BreaksLeaf, BreaksMetric};
+    use crate::breaks::{
+        hard_line_of_break, visual_row_to_byte, BreakBuilder, Breaks, BreaksInfo, BreaksLeaf,
+        BreaksMetric,
+    };
     use crate::interval::Interval;
+    use crate::rope::Rope;
     use crate::tree::{Cursor, Node};
 
     fn gen(n: usize) -> Breaks {
@@ -344,4 +424,103 @@ mod tests {
         assert_eq!(breaks.count_breaks_in_range(9..9), 0);
         assert_eq!(breaks.count_breaks_in_range(9..8), 0);
     }
+
+    fn build_manually(total_len: usize, offsets: &[usize]) -> Breaks {
+        let mut builder = BreakBuilder::new();
+        let mut prev = 0;
+        for &offset in offsets {
+            if offset > prev {
+                builder.add_break(offset - prev);
+                prev = offset;
+            }
+        }
+        if prev < total_len {
+            builder.add_no_break(total_len - prev);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn extend_from_offsets_matches_manual() {
+        let many: Vec<usize> = (1..500).map(|i| i * 3).collect();
+        let layouts: Vec<(usize, Vec<usize>)> = vec![
+            (0, vec![]),
+            (10, vec![]),
+            (10, vec![10]),
+            (10, vec![3, 7]),
+            (10, vec![0, 3, 3, 7]),
+            (2000, many),
+        ];
+        for (total_len, offsets) in layouts {
+            let mut builder = BreakBuilder::new();
+            builder.extend_from_offsets(total_len, &offsets);
+            let breaks = builder.build();
+            let expected = build_manually(total_len, &offsets);
+            assert_eq!(breaks.len(), total_len);
+            assert_eq!(breaks.measure::<BreaksMetric>(), expected.measure::<BreaksMetric>());
+            for offset in 0..=total_len {
+                assert_eq!(breaks.count_breaks_up_to(offset), expected.count_breaks_up_to(offset));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "break offsets must be sorted")]
+    fn extend_from_offsets_unsorted() {
+        let mut builder = BreakBuilder::new();
+        builder.extend_from_offsets(10, &[5, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds length")]
+    fn extend_from_offsets_out_of_bounds() {
+        let mut builder = BreakBuilder::new();
+        builder.extend_from_offsets(10, &[11]);
+    }
+
+    #[test]
+    fn visual_rows_mix_hard_and_soft_breaks() {
+        let rope = Rope::from("hello world\nfoo bar baz\nend");
+        let mut b = BreakBuilder::new();
+        b.extend_from_offsets(rope.len(), &[6, 20]);
+        let breaks = b.build();
+
+        let rows: Vec<usize> = (0..7).map(|row| visual_row_to_byte(&rope, &breaks, row)).collect();
+        assert_eq!(rows, vec![0, 6, 12, 20, 24, 27, 27]);
+    }
+
+    #[test]
+    fn visual_rows_match_merged_offsets() {
+        let line = "a line of text\n";
+        let rope = Rope::from(line.repeat(40));
+        let soft: Vec<usize> = (0..rope.len()).filter(|i| i % 15 == 7).collect();
+        let mut b = BreakBuilder::new();
+        b.extend_from_offsets(rope.len(), &soft);
+        let breaks = b.build();
+
+        let mut merged: Vec<usize> = (1..=40).map(|i| i * line.len()).chain(soft).collect();
+        merged.sort_unstable();
+        for (i, &offset) in merged.iter().enumerate() {
+            assert_eq!(visual_row_to_byte(&rope, &breaks, i + 1), offset);
+        }
+    }
+
+    #[test]
+    fn hard_lines_of_soft_breaks() {
+        // lines start at 0, 12, 14 and 38
+        let rope = Rope::from("hello world\n\nthe quick brown fox ju\nmps over");
+        let mut b = BreakBuilder::new();
+        b.extend_from_offsets(rope.len(), &[6, 18, 24, 30, 42]);
+        let breaks = b.build();
+
+        let lines: Vec<usize> = (0..5).map(|i| hard_line_of_break(&rope, &breaks, i)).collect();
+        assert_eq!(lines, vec![0, 2, 2, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn hard_line_of_missing_break() {
+        let rope = Rope::from("no breaks here");
+        hard_line_of_break(&rope, &Breaks::new_no_break(rope.len()), 0);
+    }
 }
@@ -98,6 +98,26 @@ pub fn ne_idx_rev(one: &[u8], two: &[u8]) -> Option<usize> {
     ne_idx_rev_fallback(one, two)
 }
 
+/// Returns the length of the longest common prefix of `one` and `two`, in
+/// bytes.
+///
+/// This uses SIMD comparison where the CPU supports it. The result may fall
+/// inside a multi-byte UTF-8 sequence when the inputs are text.
+pub fn common_prefix_len(one: &[u8], two: &[u8]) -> usize {
+    ne_idx(one, two).unwrap_or_else(|| one.len().min(two.len()))
+}
+
+/// Returns the length of the longest common suffix of `one` and `two`, in
+/// bytes.
+///
+/// As with [`common_prefix_len`], the result may fall inside a multi-byte
+/// UTF-8 sequence.
+///
+/// [`common_prefix_len`]: fn.common_prefix_len.html
+pub fn common_suffix_len(one: &[u8], two: &[u8]) -> usize {
+    ne_idx_rev(one, two).unwrap_or_else(|| one.len().min(two.len()))
+}
+
 #[doc(hidden)]
 #[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "avx2")]
@@ -727,4 +747,25 @@ mod tests {
             assert_eq!(ne_idx_avx(one.as_bytes(), two.as_bytes()), Some(38));
         }
     }
+
+    #[test]
+    fn common_prefix_and_suffix_len() {
+        let head = "x".repeat(37);
+        let one = format!("{}😀{}", head, "y".repeat(40));
+        let two = format!("{}😁{}", head, "y".repeat(40));
+        // 😀 and 😁 differ only in their last byte
+        let diff = head.len() + 3;
+        for start in 0..5 {
+            let (one, two) = (&one.as_bytes()[start..], &two.as_bytes()[start..]);
+            assert_eq!(common_prefix_len(one, two), diff - start);
+            assert_eq!(common_suffix_len(one, two), 40);
+            assert_eq!(common_prefix_len(one, one), one.len());
+            assert_eq!(common_suffix_len(one, one), one.len());
+        }
+
+        assert_eq!(common_prefix_len(b"abc", b"abcdef"), 3);
+        assert_eq!(common_suffix_len(b"def", b"abcdef"), 3);
+        assert_eq!(common_prefix_len(b"", b"abc"), 0);
+        assert_eq!(common_suffix_len(b"xbc", b"abc"), 2);
+    }
 }
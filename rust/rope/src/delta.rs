@@ -50,6 +50,37 @@ pub struct Delta<N: NodeInfo<L>, L: Leaf> {
 #[derive(Clone)]
 pub struct InsertDelta<N: NodeInfo<L>, L: Leaf>(Delta<N, L>);
 
+/// The reason a delta could not be applied by [`Delta::try_apply`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeltaError {
+    /// The delta's `base_len` does not match the length of the node it was
+    /// applied to.
+    BaseLenMismatch { delta_len: usize, base_len: usize },
+    /// A copy references a range that is inverted or extends past the end
+    /// of the base.
+    CopyOutOfRange { beg: usize, end: usize, base_len: usize },
+    /// A copy starts before the end of the previous copy.
+    CopyOutOfOrder { prev_end: usize, beg: usize },
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DeltaError::BaseLenMismatch { delta_len, base_len } => {
+                write!(f, "Delta base_len {} does not match base length {}", delta_len, base_len)
+            }
+            DeltaError::CopyOutOfRange { beg, end, base_len } => {
+                write!(f, "Copy [{}, {}) is out of range for base length {}", beg, end, base_len)
+            }
+            DeltaError::CopyOutOfOrder { prev_end, beg } => {
+                write!(f, "Copy starting at {} overlaps previous copy ending at {}", beg, prev_end)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeltaError {}
+
 impl<N: NodeInfo<L>, L: Leaf> Delta<N, L> {
     #[cfg_attr(not(feature = "serde"), allow(dead_code))]
     pub(crate) fn base_len(&self) -> usize {
@@ -190,6 +221,204 @@ impl<N: NodeInfo<L>, L: Leaf> Delta<N, L> {
         b.build()
     }
 
+    /// Like [`apply`](#method.apply), but first checks that the delta is
+    /// well-formed with respect to `base`, returning an error instead of
+    /// panicking. Use this for deltas from untrusted sources, such as peers.
+    pub fn try_apply(&self, base: &Node<N, L>) -> Result<Node<N, L>, DeltaError> {
+        let base_len = base.len();
+        if self.base_len != base_len {
+            return Err(DeltaError::BaseLenMismatch { delta_len: self.base_len, base_len });
+        }
+        let mut prev_end = 0;
+        for elem in &self.els {
+            if let DeltaElement::Copy(beg, end) = *elem {
+                if beg > end || end > base_len {
+                    return Err(DeltaError::CopyOutOfRange { beg, end, base_len });
+                }
+                if beg < prev_end {
+                    return Err(DeltaError::CopyOutOfOrder { prev_end, beg });
+                }
+                prev_end = end;
+            }
+        }
+        Ok(self.apply(base))
+    }
+
+    /// Applies the delta on a best-effort basis to a `base` whose length may
+    /// not match the one the delta was built for.
+    ///
+    /// Copied regions are clamped to `base.len()`, and the length check made
+    /// by [`apply`](#method.apply) and [`try_apply`](#method.try_apply) is
+    /// skipped. This is for lenient recovery, where applying part of an edit
+    /// is preferable to failing; prefer `try_apply` anywhere else.
+    ///
+    /// **This can silently lose or misplace text.** If `base` is shorter than
+    /// expected, copies past its end are truncated or dropped, and if it is
+    /// longer, its tail beyond the delta's last copy is discarded. Nothing
+    /// checks that the copied regions still mean what they did in the
+    /// delta's original base.
+    pub fn apply_clamped(&self, base: &Node<N, L>) -> Node<N, L> {
+        let base_len = base.len();
+        let mut b = TreeBuilder::new();
+        for elem in &self.els {
+            match *elem {
+                DeltaElement::Copy(beg, end) => {
+                    let end = end.min(base_len);
+                    let beg = beg.min(end);
+                    if beg < end {
+                        b.push_slice(base, Interval::new(beg, end));
+                    }
+                }
+                DeltaElement::Insert(ref n) => b.push(n.clone()),
+            }
+        }
+        b.build()
+    }
+
+    /// Returns the part of this delta that applies to the region `iv` of its
+    /// base, as a delta from that region (so with a `base_len` of
+    /// `iv.size()`) to the text that replaces it.
+    ///
+    /// Copies are clipped to `iv` and shifted to be relative to its start,
+    /// so deletions that straddle a boundary are clipped too. An insert is
+    /// kept if it is placed (after the preceding copy) within `iv`, including
+    /// at either end, and dropped otherwise. A replacement that straddles a
+    /// boundary therefore keeps its inserted text only if it begins inside
+    /// `iv`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `iv` extends past this delta's `base_len`.
+    pub fn restrict_to(&self, iv: Interval) -> Delta<N, L> {
+        assert!(iv.end() <= self.base_len, "interval extends past the delta's base");
+        let mut els = Vec::new();
+        let mut base_pos = 0;
+        for elem in &self.els {
+            match *elem {
+                DeltaElement::Copy(beg, end) => {
+                    let clipped = Interval::new(beg, end).intersect(iv);
+                    if !clipped.is_empty() {
+                        let local = clipped.translate_neg(iv.start());
+                        els.push(DeltaElement::Copy(local.start(), local.end()));
+                    }
+                    base_pos = end;
+                }
+                DeltaElement::Insert(ref n) => {
+                    if iv.start() <= base_pos && base_pos <= iv.end() {
+                        els.push(DeltaElement::Insert(n.clone()));
+                    }
+                }
+            }
+        }
+        Delta { els, base_len: iv.size() }
+    }
+
+    /// Lifts a delta computed for a region of a larger document to the whole
+    /// document, where `prefix_len` unchanged units precede the region and
+    /// `suffix_len` follow it.
+    ///
+    /// Copies are shifted by `prefix_len`, and copies of the prefix and
+    /// suffix are added at either end, merged with adjacent copies. This is
+    /// the inverse of [`restrict_to`](#method.restrict_to) for a delta that
+    /// leaves the text outside the region untouched.
+    pub fn widen(&self, prefix_len: usize, suffix_len: usize) -> Delta<N, L> {
+        let base_len = prefix_len + self.base_len + suffix_len;
+        let shifted = self.els.iter().map(|elem| match *elem {
+            DeltaElement::Copy(beg, end) => DeltaElement::Copy(beg + prefix_len, end + prefix_len),
+            DeltaElement::Insert(ref n) => DeltaElement::Insert(n.clone()),
+        });
+        let prefix = DeltaElement::Copy(0, prefix_len);
+        let suffix = DeltaElement::Copy(base_len - suffix_len, base_len);
+        let mut els: Vec<DeltaElement<N, L>> = Vec::with_capacity(self.els.len() + 2);
+        for elem in std::iter::once(prefix).chain(shifted).chain(std::iter::once(suffix)) {
+            if let DeltaElement::Copy(beg, end) = elem {
+                if beg == end {
+                    continue;
+                }
+                if let Some(DeltaElement::Copy(_, prev_end)) = els.last_mut() {
+                    if *prev_end == beg {
+                        *prev_end = end;
+                        continue;
+                    }
+                }
+            }
+            els.push(elem);
+        }
+        Delta { els, base_len }
+    }
+
+    /// Returns an equivalent delta in minimal form: contiguous copies are
+    /// merged into one, consecutive inserts are concatenated, and empty
+    /// elements are dropped.
+    pub fn coalesce(self) -> Delta<N, L> {
+        let mut els: Vec<DeltaElement<N, L>> = Vec::with_capacity(self.els.len());
+        for elem in self.els {
+            match (els.last_mut(), elem) {
+                (_, DeltaElement::Copy(beg, end)) if beg == end => (),
+                (_, DeltaElement::Insert(ref n)) if n.is_empty() => (),
+                (Some(DeltaElement::Copy(_, prev_end)), DeltaElement::Copy(beg, end))
+                    if *prev_end == beg =>
+                {
+                    *prev_end = end;
+                }
+                (Some(DeltaElement::Insert(prev)), DeltaElement::Insert(n)) => {
+                    *prev = Node::concat(prev.clone(), n);
+                }
+                (_, elem) => els.push(elem),
+            }
+        }
+        Delta { els, base_len: self.base_len }
+    }
+
+    /// Returns a delta equivalent to applying `self` and then `other`, which
+    /// must be based on the result of `self`.
+    ///
+    /// The copies in `other` are in increasing order, so both element lists
+    /// are walked once: O(n + m) in their lengths.
+    pub(crate) fn compose(&self, other: &Delta<N, L>) -> Delta<N, L> {
+        assert_eq!(self.new_document_len(), other.base_len, "composed deltas do not line up");
+        let mut els = Vec::new();
+        // `ix` is the first of our elements that may still be copied, and
+        // `offset` is where it starts in our output
+        let mut ix = 0;
+        let mut offset = 0;
+        for elem in &other.els {
+            match *elem {
+                DeltaElement::Copy(beg, end) => {
+                    // map [beg, end) of our output back through our elements
+                    while let Some(own) = self.els.get(ix) {
+                        let len = match *own {
+                            DeltaElement::Copy(b, e) => e - b,
+                            DeltaElement::Insert(ref n) => n.len(),
+                        };
+                        if offset >= end {
+                            break;
+                        }
+                        let (start, stop) = (beg.max(offset), end.min(offset + len));
+                        if start < stop {
+                            els.push(match *own {
+                                DeltaElement::Copy(b, _) => {
+                                    DeltaElement::Copy(b + start - offset, b + stop - offset)
+                                }
+                                DeltaElement::Insert(ref n) => {
+                                    DeltaElement::Insert(n.subseq(start - offset..stop - offset))
+                                }
+                            });
+                        }
+                        if offset + len > end {
+                            // the rest of this element may be copied again
+                            break;
+                        }
+                        offset += len;
+                        ix += 1;
+                    }
+                }
+                DeltaElement::Insert(ref n) => els.push(DeltaElement::Insert(n.clone())),
+            }
+        }
+        Delta { els, base_len: self.base_len }.coalesce()
+    }
+
     /// Factor the delta into an insert-only delta and a subset representing deletions.
     /// Applying the insert then the delete yields the same result as the original delta:
     ///
@@ -791,7 +1020,7 @@ impl<'a, N: NodeInfo<L>, L: Leaf> Iterator for ElementTripleIter<'a, N, L> {
 
 #[cfg(test)]
 mod tests {
-    use crate::delta::{Builder, Delta, DeltaElement, DeltaRegion};
+    use crate::delta::{Builder, Delta, DeltaElement, DeltaError, DeltaRegion};
     use crate::interval::Interval;
     use crate::rope::{Rope, RopeInfo};
     use crate::test_helpers::find_deletions;
@@ -971,6 +1200,156 @@ mod tests {
         let d = Delta::simple_edit(Interval::new(10, 10), Rope::from("+"), TEST_STR.len());
         assert_eq!(Some(Rope::from("+")).as_ref(), d.as_simple_insert());
     }
+
+    #[test]
+    fn try_apply() {
+        let base = Rope::from("hello world");
+        let d = Delta::simple_edit(Interval::new(1, 9), Rope::from("era"), 11);
+        assert_eq!(String::from(d.try_apply(&base).unwrap()), "herald");
+
+        let out_of_range: Delta<RopeInfo, String> =
+            Delta { els: vec![DeltaElement::Copy(0, 5), DeltaElement::Copy(6, 12)], base_len: 11 };
+        assert_eq!(
+            out_of_range.try_apply(&base).unwrap_err(),
+            DeltaError::CopyOutOfRange { beg: 6, end: 12, base_len: 11 }
+        );
+
+        let out_of_order: Delta<RopeInfo, String> =
+            Delta { els: vec![DeltaElement::Copy(6, 11), DeltaElement::Copy(0, 5)], base_len: 11 };
+        assert_eq!(
+            out_of_order.try_apply(&base).unwrap_err(),
+            DeltaError::CopyOutOfOrder { prev_end: 11, beg: 0 }
+        );
+
+        assert_eq!(
+            d.try_apply(&Rope::from("hello")).unwrap_err(),
+            DeltaError::BaseLenMismatch { delta_len: 11, base_len: 5 }
+        );
+    }
+
+    #[test]
+    fn coalesce() {
+        let base = Rope::from("hello world");
+        let d: Delta<RopeInfo, String> = Delta {
+            els: vec![
+                DeltaElement::Copy(0, 2),
+                DeltaElement::Copy(2, 2),
+                DeltaElement::Copy(2, 5),
+                DeltaElement::Insert(Rope::from(", ")),
+                DeltaElement::Insert(Rope::from("")),
+                DeltaElement::Insert(Rope::from("big ")),
+                DeltaElement::Copy(6, 8),
+                DeltaElement::Copy(8, 11),
+            ],
+            base_len: 11,
+        };
+        let expected = String::from(d.apply(&base));
+        let d = d.coalesce();
+        assert_eq!(d.els.len(), 3);
+        assert!(matches!(d.els[0], DeltaElement::Copy(0, 5)));
+        assert!(matches!(d.els[1], DeltaElement::Insert(ref n) if String::from(n) == ", big "));
+        assert!(matches!(d.els[2], DeltaElement::Copy(6, 11)));
+        assert_eq!(String::from(d.apply(&base)), expected);
+        assert_eq!(expected, "hello, big world");
+    }
+
+    #[test]
+    fn compose() {
+        let base = Rope::from("hello world");
+        let first = Delta::simple_edit(Interval::new(5, 5), Rope::from(", big"), base.len());
+        let mid = first.apply(&base);
+        // delete part of the inserted text along with some of the original
+        let second = Delta::simple_edit(Interval::new(2, 8), Rope::from("y"), mid.len());
+        let composed = first.compose(&second);
+        assert_eq!(composed.base_len, base.len());
+        assert_eq!(String::from(composed.apply(&base)), String::from(second.apply(&mid)));
+        assert_eq!(String::from(composed.apply(&base)), "heyig world");
+
+        // one of our elements split across several of the other's copies,
+        // and a long chain of edits folded together one at a time
+        let second = Delta::simple_edit(Interval::new(7, 9), Rope::from("-"), mid.len());
+        let third = first.compose(&second);
+        assert_eq!(String::from(third.apply(&base)), "hello, -g world");
+        let mut text = base.clone();
+        let mut chain = Delta::simple_edit(Interval::new(0, 0), Rope::from(""), base.len());
+        for i in 0..200 {
+            let at = (i * 7) % (text.len() + 1);
+            let end = (at + i % 3).min(text.len());
+            let edit = Delta::simple_edit(Interval::new(at, end), Rope::from("ab"), text.len());
+            text = edit.apply(&text);
+            chain = chain.compose(&edit);
+        }
+        assert_eq!(String::from(chain.apply(&base)), String::from(text));
+    }
+
+    #[test]
+    fn widen() {
+        let doc = Rope::from("fn main() { let x = 1; }");
+        let region = Interval::new(12, 22);
+        let local = doc.slice(region);
+        assert_eq!(String::from(&local), "let x = 1;");
+        let d = Delta::simple_edit(Interval::new(4, 5), Rope::from("answer"), local.len());
+
+        let wide = d.widen(region.start(), doc.len() - region.end());
+        assert_eq!(wide.base_len, doc.len());
+        assert_eq!(String::from(wide.apply(&doc)), "fn main() { let answer = 1; }");
+        assert_eq!(wide.els.len(), 3);
+        assert!(matches!(wide.els[0], DeltaElement::Copy(0, 16)));
+        assert!(matches!(wide.els[2], DeltaElement::Copy(17, 24)));
+        assert_eq!(format!("{:?}", wide.restrict_to(region)), format!("{:?}", d));
+
+        // an insert at the very start stays between the prefix and the region
+        let d = Delta::simple_edit(Interval::new(0, 0), Rope::from("/*a*/"), local.len());
+        let wide = d.widen(12, 2);
+        assert_eq!(String::from(wide.apply(&doc)), "fn main() { /*a*/let x = 1; }");
+
+        let d = Delta::simple_edit(Interval::new(0, 3), Rope::from(""), local.len());
+        assert_eq!(format!("{:?}", d.widen(0, 0)), format!("{:?}", d));
+    }
+
+    #[test]
+    fn apply_clamped() {
+        // built for "hello world\n", applied to "hello world"
+        let d = Delta::simple_edit(Interval::new(0, 5), Rope::from("goodbye"), 12);
+        let base = Rope::from("hello world");
+        assert!(d.try_apply(&base).is_err());
+        assert_eq!(String::from(d.apply_clamped(&base)), "goodbye world");
+
+        let exact = Rope::from("hello world\n");
+        assert_eq!(String::from(d.apply_clamped(&exact)), String::from(d.apply(&exact)));
+
+        // a copy entirely past the end is dropped
+        let d =
+            Delta { els: vec![DeltaElement::Copy(0, 2), DeltaElement::Copy(20, 25)], base_len: 25 };
+        assert_eq!(String::from(d.apply_clamped(&base)), "he");
+    }
+
+    #[test]
+    fn restrict_to() {
+        let base = Rope::from(TEST_STR);
+        let mut builder = Builder::new(base.len());
+        builder.replace(Interval::new(2, 5), Rope::from("<one>"));
+        builder.replace(Interval::new(20, 24), Rope::from("<two>"));
+        builder.delete(Interval::new(40, 45));
+        let delta = builder.build();
+
+        let iv = Interval::new(15, 30);
+        let restricted = delta.restrict_to(iv);
+        assert_eq!(restricted.base_len, iv.size());
+        let targeted = Delta::simple_edit(Interval::new(5, 9), Rope::from("<two>"), iv.size());
+        let region = base.slice(iv);
+        assert_eq!(String::from(restricted.apply(&region)), String::from(targeted.apply(&region)));
+        assert_eq!(String::from(restricted.apply(&region)), "FGHIJ<two>OPQRST");
+
+        // a straddling deletion is clipped; a straddling insert is kept only
+        // if it starts inside the interval
+        let region = base.slice(42..50);
+        assert_eq!(String::from(delta.restrict_to(Interval::new(42, 50)).apply(&region)), "jklmn");
+        let region = base.slice(3..10);
+        assert_eq!(String::from(delta.restrict_to(Interval::new(3, 10)).apply(&region)), "56789");
+        let region = base.slice(0..3);
+        assert_eq!(String::from(delta.restrict_to(Interval::new(0, 3)).apply(&region)), "01<one>");
+    }
 }
 
 #[cfg(all(test, feature = "serde"))]
@@ -15,10 +15,14 @@
 //! Computing deltas between two ropes.
 
 use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
 
 use crate::compare::RopeScanner;
-use crate::delta::{Delta, DeltaElement};
+use crate::delta::{Builder, Delta, DeltaElement};
 use crate::interval::Interval;
 use crate::rope::{LinesMetric, Rope, RopeDelta, RopeInfo};
 use crate::tree::{Leaf, Node, NodeInfo};
@@ -30,6 +34,24 @@ where
     L: Leaf,
 {
     fn compute_delta(base: &Node<N, L>, target: &Node<N, L>) -> Delta<N, L>;
+
+    /// Like [`compute_delta`], but bounds the work done to about `max_ops`
+    /// steps, falling back to a coarser delta if the budget runs out. The
+    /// result always transforms `base` into `target`.
+    ///
+    /// What counts as a step is up to the implementation. The default
+    /// implementation ignores the budget; strategies that can bound their
+    /// work should override it.
+    ///
+    /// [`compute_delta`]: #tymethod.compute_delta
+    fn compute_delta_budgeted(
+        base: &Node<N, L>,
+        target: &Node<N, L>,
+        max_ops: usize,
+    ) -> Delta<N, L> {
+        let _ = max_ops;
+        Self::compute_delta(base, target)
+    }
 }
 
 /// The minimum length of non-whitespace characters in a line before
@@ -53,6 +75,43 @@ pub struct LineHashDiff;
 
 impl Diff<RopeInfo, String> for LineHashDiff {
     fn compute_delta(base: &Rope, target: &Rope) -> RopeDelta {
+        LineHashDiff::compute_delta_with_build_hasher(base, target, RandomState::new(), usize::MAX)
+    }
+
+    /// Counts one step per hash map insert or lookup, that is, per line of
+    /// either document long enough to be matched. When the budget is
+    /// exceeded, the result copies the unchanged prefix and suffix found by
+    /// the initial scan and replaces everything between them.
+    fn compute_delta_budgeted(base: &Rope, target: &Rope, max_ops: usize) -> RopeDelta {
+        LineHashDiff::compute_delta_with_build_hasher(base, target, RandomState::new(), max_ops)
+    }
+}
+
+impl LineHashDiff {
+    /// Like [`compute_delta`], but hashes lines with hashers built by
+    /// `hash_builder` instead of a fresh `RandomState`. Pass a keyed or
+    /// seeded builder to control the hash; wrap a plain `Hasher` type in
+    /// `BuildHasherDefault` to use it unkeyed.
+    ///
+    /// Candidate lines are always compared byte for byte after a hash hit, so
+    /// the choice of hasher only affects speed: even a hasher with many
+    /// collisions produces a delta that yields exactly `target`.
+    ///
+    /// [`compute_delta`]: trait.Diff.html#tymethod.compute_delta
+    pub fn compute_delta_with_hasher<S: BuildHasher>(
+        base: &Rope,
+        target: &Rope,
+        hash_builder: S,
+    ) -> RopeDelta {
+        LineHashDiff::compute_delta_with_build_hasher(base, target, hash_builder, usize::MAX)
+    }
+
+    fn compute_delta_with_build_hasher<S: BuildHasher>(
+        base: &Rope,
+        target: &Rope,
+        hash_builder: S,
+        max_ops: usize,
+    ) -> RopeDelta {
         let mut builder = DiffBuilder::default();
 
         // before doing anything, scan top down and bottom up for like-ness.
@@ -81,7 +140,20 @@ impl Diff<RopeInfo, String> for LineHashDiff {
             return builder.to_delta(base, target);
         }
 
-        let line_hashes = make_line_hashes(base, MIN_SIZE);
+        // over budget: keep the common prefix and suffix, replace the rest
+        let coarse = |mut builder: DiffBuilder| {
+            if diff_end > 0 {
+                builder.copy(base.len() - diff_end, target_end, diff_end);
+            }
+            builder.to_delta(base, target)
+        };
+
+        // the map compares keys on a hash hit, so colliding lines never match
+        let mut ops = 0;
+        let line_hashes = match make_line_hashes(base, MIN_SIZE, hash_builder, &mut ops, max_ops) {
+            Some(line_hashes) => line_hashes,
+            None => return coarse(builder),
+        };
 
         let line_count = target.measure::<LinesMetric>() + 1;
         let mut matches = Vec::with_capacity(line_count);
@@ -93,6 +165,10 @@ impl Diff<RopeInfo, String> for LineHashDiff {
         for line in target.lines_raw(start_offset..target_end) {
             let non_ws = non_ws_offset(&line);
             if line.len() - non_ws >= MIN_SIZE {
+                ops += 1;
+                if ops > max_ops {
+                    return coarse(builder);
+                }
                 if let Some(base_off) = line_hashes.get(&line[non_ws..]) {
                     let targ_off = targ_line_offset + non_ws;
                     matches.push((start_offset + targ_off, *base_off));
@@ -143,6 +219,358 @@ impl Diff<RopeInfo, String> for LineHashDiff {
 
         builder.to_delta(base, target)
     }
+
+    /// Computes a delta from `base` to `target`, reading both a line at a time.
+    ///
+    /// This is intended for documents too large to comfortably hold twice.
+    /// Only whole lines are matched, and matches are chosen greedily rather
+    /// than by the longest increasing subsequence, so the result may contain
+    /// larger inserts than [`compute_delta`] would produce. Lines shorter than
+    /// the matching threshold are only copied when they continue the previous
+    /// copied region.
+    ///
+    /// Memory use is proportional to the total size of the *distinct* lines
+    /// of `base` (plus one offset per base line), along with the inserted text
+    /// carried by the resulting delta; `target` is never held in full.
+    ///
+    /// Returns an error if either reader fails or yields invalid UTF-8.
+    ///
+    /// [`compute_delta`]: trait.Diff.html#tymethod.compute_delta
+    pub fn compute_delta_streaming<R: BufRead>(
+        mut base: R,
+        mut target: R,
+    ) -> io::Result<RopeDelta> {
+        let mut base_lines: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut base_len = 0;
+        let mut line = String::new();
+        while base.read_line(&mut line)? > 0 {
+            let len = line.len();
+            match base_lines.get_mut(&line) {
+                Some(offsets) => offsets.push(base_len),
+                None => {
+                    base_lines.insert(line.clone(), vec![base_len]);
+                }
+            }
+            base_len += len;
+            line.clear();
+        }
+
+        let mut els = Vec::new();
+        let mut pending = String::new();
+        // the base offset just past the most recent copy
+        let mut base_pos = 0;
+        while target.read_line(&mut line)? > 0 {
+            let offsets = base_lines.get(&line).map(Vec::as_slice).unwrap_or(&[]);
+            let non_ws = non_ws_offset(&line);
+            let matched = if line.len() - non_ws >= MIN_SIZE {
+                let ix = offsets.partition_point(|&off| off < base_pos);
+                offsets.get(ix).copied()
+            } else {
+                offsets.binary_search(&base_pos).ok().map(|_| base_pos)
+            };
+            match matched {
+                Some(base_off) => {
+                    if !pending.is_empty() {
+                        els.push(DeltaElement::Insert(Rope::from(std::mem::take(&mut pending))));
+                    }
+                    let end = base_off + line.len();
+                    match els.last_mut() {
+                        Some(DeltaElement::Copy(_, prev_end)) if *prev_end == base_off => {
+                            *prev_end = end
+                        }
+                        _ => els.push(DeltaElement::Copy(base_off, end)),
+                    }
+                    base_pos = end;
+                }
+                None => pending.push_str(&line),
+            }
+            line.clear();
+        }
+        if !pending.is_empty() {
+            els.push(DeltaElement::Insert(Rope::from(pending)));
+        }
+        Ok(Delta { els, base_len })
+    }
+}
+
+impl Delta<RopeInfo, String> {
+    /// Rebases this delta, which applies to `old`, so that it applies to `new`.
+    ///
+    /// This is intended for keeping an unsaved edit when the underlying file is
+    /// reloaded from disk. The change from `old` to `new` is computed with
+    /// [`LineHashDiff`], and each region edited by this delta is carried over to
+    /// `new`. Returns `None` if any edited region (including the points at which
+    /// text is inserted) was itself modified by the reload, or is directly
+    /// adjacent to such a modification.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `old.len()` does not equal this delta's `base_len`.
+    pub fn try_rebase_literal(&self, old: &Rope, new: &Rope) -> Option<RopeDelta> {
+        assert_eq!(self.base_len, old.len(), "delta does not apply to the old rope");
+        let reload = LineHashDiff::compute_delta(old, new);
+        let mut builder = Builder::new(new.len());
+        for (iv, text) in literal_changes(self) {
+            let start = map_untouched(&reload, iv)?;
+            builder.replace(Interval::new(start, start + iv.size()), text);
+        }
+        Some(builder.build())
+    }
+
+    /// Writes the result of applying this delta to `base` to `out`, without
+    /// building the resulting rope.
+    ///
+    /// Copied regions of `base` and inserted text are both written a chunk at
+    /// a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `base.len()` does not equal this delta's `base_len`.
+    pub fn apply_to_writer<W: Write>(&self, base: &Rope, mut out: W) -> io::Result<()> {
+        assert_eq!(self.base_len, base.len(), "delta does not apply to base");
+        for el in &self.els {
+            let chunks = match *el {
+                DeltaElement::Copy(beg, end) => base.iter_chunks(beg..end),
+                DeltaElement::Insert(ref n) => n.iter_chunks(..),
+            };
+            for chunk in chunks {
+                out.write_all(chunk.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders this delta as a unified-diff-like listing against `base`, with
+    /// `context` unchanged lines around each change.
+    ///
+    /// Lines of `base` that the delta rewrites are prefixed with `-`, the text
+    /// replacing them with `+`, and context lines with a space. This is meant
+    /// for logs and debugging output rather than for patch tools.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `base.len()` does not equal this delta's `base_len`.
+    pub fn render_unified(&self, base: &Rope, context: usize) -> String {
+        assert_eq!(self.base_len, base.len(), "delta does not apply to base");
+        let changes = line_changes(self, base);
+        let line_count = base.line_of_offset(base.len())
+            + if base.len() > base.offset_of_line(base.line_of_offset(base.len())) { 1 } else { 0 };
+        let base_lines = |lines: Range<usize>| {
+            base.slice_to_cow(base.offset_of_line(lines.start)..base.offset_of_line(lines.end))
+        };
+
+        let mut out = String::new();
+        // net number of lines added by the changes already rendered
+        let mut shift = 0isize;
+        let mut ix = 0;
+        while ix < changes.len() {
+            let mut end = ix + 1;
+            while end < changes.len()
+                && changes[end].0.start <= changes[end - 1].0.end + 2 * context
+            {
+                end += 1;
+            }
+            let hunk = &changes[ix..end];
+            let start_line = hunk[0].0.start.saturating_sub(context);
+            let end_line = (hunk[hunk.len() - 1].0.end + context).min(line_count);
+
+            let mut body = String::new();
+            let mut line = start_line;
+            let mut new_count = 0;
+            for (lines, text) in hunk {
+                new_count += push_prefixed_lines(&mut body, ' ', &base_lines(line..lines.start));
+                push_prefixed_lines(&mut body, '-', &base_lines(lines.clone()));
+                new_count += push_prefixed_lines(&mut body, '+', text);
+                line = lines.end;
+            }
+            new_count += push_prefixed_lines(&mut body, ' ', &base_lines(line..end_line));
+
+            let new_start = (start_line as isize + shift) as usize;
+            out.push_str(&format!(
+                "@@ -{},{} +{},{} @@\n",
+                start_line + 1,
+                end_line - start_line,
+                new_start + 1,
+                new_count
+            ));
+            out.push_str(&body);
+            shift += new_count as isize - (end_line - start_line) as isize;
+            ix = end;
+        }
+        out
+    }
+
+    /// Splits this delta into one delta per affected line of `base`, for
+    /// protocols that transmit changes a line at a time.
+    ///
+    /// Each entry is a 0-based line number of `base` and a delta from that
+    /// line's contents, including its line ending, to what replaces them.
+    /// Untouched lines are omitted. Inserted text belongs to the line on
+    /// which it is inserted (text inserted at the very start of a line goes
+    /// to that line, not to the end of the previous one), and a deletion
+    /// spanning several lines deletes its part of each; so text that joins
+    /// lines appears as the line ending being deleted from one line, and
+    /// an insert containing newlines as new lines within one line's result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `base` differs from the delta's base length.
+    pub fn split_by_lines(&self, base: &Rope) -> Vec<(usize, RopeDelta)> {
+        assert_eq!(self.base_len, base.len(), "delta does not apply to base");
+        let line_bounds = |line: usize| {
+            let start = base.offset_of_line(line);
+            let end = if line < base.measure::<LinesMetric>() {
+                base.offset_of_line(line + 1)
+            } else {
+                base.len()
+            };
+            Interval::new(start, end)
+        };
+        let mut base_pos = 0;
+        let mut inserted: Option<Rope> = None;
+        let mut changes = Vec::new();
+        // an empty copy at the end flushes a trailing change
+        let sentinel = DeltaElement::Copy(base.len(), base.len());
+        for elem in self.els.iter().chain(Some(&sentinel)) {
+            match elem {
+                DeltaElement::Copy(beg, end) => {
+                    let deleted = Interval::new(base_pos, *beg);
+                    if !deleted.is_empty() || inserted.is_some() {
+                        changes.push((deleted, inserted.take()));
+                    }
+                    base_pos = *end;
+                }
+                DeltaElement::Insert(n) => {
+                    inserted = Some(match inserted.take() {
+                        Some(prev) => prev + n.clone(),
+                        None => n.clone(),
+                    });
+                }
+            }
+        }
+
+        let mut lines: Vec<(usize, Interval, Builder<RopeInfo, String>)> = Vec::new();
+        for (deleted, mut text) in changes {
+            let first = base.line_of_offset(deleted.start());
+            let last =
+                if deleted.is_empty() { first } else { base.line_of_offset(deleted.end() - 1) };
+            for line in first..=last {
+                if lines.last().map(|&(last, _, _)| last) != Some(line) {
+                    let bounds = line_bounds(line);
+                    lines.push((line, bounds, Builder::new(bounds.size())));
+                }
+                let (_, bounds, builder) = lines.last_mut().unwrap();
+                let local = deleted.intersect(*bounds).translate_neg(bounds.start());
+                // the inserted text goes to the first line
+                match text.take() {
+                    Some(text) => builder.replace(local, text),
+                    None => builder.delete(local),
+                }
+            }
+        }
+        lines.into_iter().map(|(line, _, builder)| (line, builder.build())).collect()
+    }
+}
+
+/// Lists the edits made by `delta` at line granularity: each entry is a range
+/// of lines in `base` and the text that replaces those lines. Edits touching
+/// the same line are combined.
+fn line_changes(delta: &RopeDelta, base: &Rope) -> Vec<(Range<usize>, String)> {
+    let at_line_start = |offset: usize| offset == base.offset_of_line(base.line_of_offset(offset));
+    let mut changes: Vec<(Range<usize>, String)> = Vec::new();
+    // the base offset up to which the last change's text has been built
+    let mut pos = 0;
+    for (iv, text) in literal_changes(delta) {
+        let first = base.line_of_offset(iv.start());
+        let last = base.line_of_offset(iv.end());
+        let end_line =
+            if at_line_start(iv.start()) && at_line_start(iv.end()) { last } else { last + 1 };
+        match changes.last_mut() {
+            Some((lines, new_text)) if first < lines.end => {
+                new_text.push_str(&base.slice_to_cow(pos..iv.start()));
+                lines.end = lines.end.max(end_line);
+            }
+            _ => {
+                close_line_change(&mut changes, base, pos);
+                let line_start = base.offset_of_line(first);
+                changes.push((first..end_line, base.slice_to_cow(line_start..iv.start()).into()));
+            }
+        }
+        changes.last_mut().unwrap().1.push_str(&String::from(text));
+        pos = iv.end();
+    }
+    close_line_change(&mut changes, base, pos);
+    changes
+}
+
+/// Completes the text of the last change with the rest of its final line.
+fn close_line_change(changes: &mut [(Range<usize>, String)], base: &Rope, pos: usize) {
+    if let Some((lines, new_text)) = changes.last_mut() {
+        new_text.push_str(&base.slice_to_cow(pos..base.offset_of_line(lines.end)));
+    }
+}
+
+/// Appends each line of `text` to `out` behind `prefix`, returning the number
+/// of lines.
+fn push_prefixed_lines(out: &mut String, prefix: char, text: &str) -> usize {
+    let mut count = 0;
+    for line in text.split_inclusive('\n') {
+        out.push(prefix);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push('\n');
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Lists the edits made by `delta`, as replacements of intervals in its base.
+fn literal_changes(delta: &RopeDelta) -> Vec<(Interval, Rope)> {
+    let mut changes = Vec::new();
+    let mut pos = 0;
+    let mut text: Option<Rope> = None;
+    for el in &delta.els {
+        match *el {
+            DeltaElement::Copy(beg, end) => {
+                if beg > pos || text.is_some() {
+                    changes.push((Interval::new(pos, beg), text.take().unwrap_or_default()));
+                }
+                pos = end;
+            }
+            DeltaElement::Insert(ref n) => {
+                text = Some(match text.take() {
+                    Some(prev) => prev + n.clone(),
+                    None => n.clone(),
+                });
+            }
+        }
+    }
+    if pos < delta.base_len || text.is_some() {
+        changes.push((Interval::new(pos, delta.base_len), text.unwrap_or_default()));
+    }
+    changes
+}
+
+/// Returns the start of `iv` in the document produced by `delta`, if `iv` lies
+/// strictly within a region that `delta` copies unchanged.
+fn map_untouched(delta: &RopeDelta, iv: Interval) -> Option<usize> {
+    let last = delta.els.len().saturating_sub(1);
+    let mut new_pos = 0;
+    for (i, el) in delta.els.iter().enumerate() {
+        match *el {
+            DeltaElement::Copy(beg, end) => {
+                let start_ok = beg < iv.start || (iv.start == 0 && i == 0);
+                let end_ok = iv.end < end || (iv.end == delta.base_len && i == last);
+                if beg <= iv.start && iv.end <= end && start_ok && end_ok {
+                    return Some(new_pos + iv.start - beg);
+                }
+                new_pos += end - beg;
+            }
+            DeltaElement::Insert(ref n) => new_pos += n.len(),
+        }
+    }
+    None
 }
 
 /// Given two ropes and the offsets of two equal bytes, finds the largest
@@ -263,12 +691,24 @@ impl DiffBuilder {
 
 /// Creates a map of lines to offsets, ignoring trailing whitespace, and only for those lines
 /// where line.len() >= min_size. Offsets refer to the first non-whitespace byte in the line.
-fn make_line_hashes(base: &Rope, min_size: usize) -> HashMap<Cow<'_, str>, usize> {
+///
+/// Each insert is counted in `ops`; returns `None` if that would exceed `max_ops`.
+fn make_line_hashes<'a, S: BuildHasher>(
+    base: &'a Rope,
+    min_size: usize,
+    hash_builder: S,
+    ops: &mut usize,
+    max_ops: usize,
+) -> Option<HashMap<Cow<'a, str>, usize, S>> {
     let mut offset = 0;
-    let mut line_hashes = HashMap::with_capacity(base.len() / 60);
+    let mut line_hashes = HashMap::with_capacity_and_hasher(base.len() / 60, hash_builder);
     for line in base.lines_raw(..) {
         let non_ws = non_ws_offset(&line);
         if line.len() - non_ws >= min_size {
+            *ops += 1;
+            if *ops > max_ops {
+                return None;
+            }
             let cow = match line {
                 Cow::Owned(ref s) => Cow::Owned(s[non_ws..].to_string()),
                 Cow::Borrowed(s) => Cow::Borrowed(&s[non_ws..]),
@@ -277,12 +717,14 @@ fn make_line_hashes(base: &Rope, min_size: usize) -> HashMap<Cow<'_, str>, usize
         }
         offset += line.len();
     }
-    line_hashes
+    Some(line_hashes)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_helpers::assert_applies;
+    use std::hash::{BuildHasherDefault, Hasher};
 
     static SMALL_ONE: &str = "This adds FixedSizeAdler32, that has a size set at construction, and keeps bytes in a cyclic buffer of that size to be removed when it fills up.
 
@@ -337,7 +779,239 @@ Currently my sense of smell (and the pain of implementing Write) might be too mu
         let two = BREAKS_STR.into();
 
         let delta = LineHashDiff::compute_delta(&one, &two);
-        let result = delta.apply(&one);
-        assert_eq!(String::from(result), String::from(two));
+        assert_applies(&delta, &one, &two);
+    }
+
+    #[test]
+    fn streaming_matches_compute_delta() {
+        let pairs = [
+            (INTERVAL_STR, BREAKS_STR),
+            (BREAKS_STR, INTERVAL_STR),
+            (SMALL_ONE, SMALL_TWO),
+            (BREAKS_STR, BREAKS_STR),
+            ("", BREAKS_STR),
+            (BREAKS_STR, ""),
+        ];
+        for (one, two) in pairs.iter() {
+            let base = Rope::from(one);
+            let target = Rope::from(two);
+            let expected = LineHashDiff::compute_delta(&base, &target).apply(&base);
+            let delta =
+                LineHashDiff::compute_delta_streaming(one.as_bytes(), two.as_bytes()).unwrap();
+            assert_eq!(delta.base_len, base.len());
+            assert_eq!(String::from(delta.apply(&base)), String::from(expected));
+        }
+
+        // identical documents should be a single copy
+        let delta =
+            LineHashDiff::compute_delta_streaming(BREAKS_STR.as_bytes(), BREAKS_STR.as_bytes())
+                .unwrap();
+        assert_eq!(delta.els.len(), 1);
+    }
+
+    #[test]
+    fn rebase_literal_untouched() {
+        let old = Rope::from(format!("{}\nhello world\n{}\n", SMALL_ONE, SMALL_ONE));
+        let new = Rope::from(format!("{}\nhello world\n{}\nappended\n", SMALL_TWO, SMALL_ONE));
+        let world = String::from(&old).find("world").unwrap();
+        let mut builder = Builder::new(old.len());
+        builder.replace(Interval::new(world, world + 5), Rope::from("there"));
+        let edit = builder.build();
+
+        let rebased = edit.try_rebase_literal(&old, &new).unwrap();
+        let expected = String::from(&new).replace("hello world", "hello there");
+        assert_eq!(expected, String::from(rebased.apply(&new)));
+    }
+
+    #[test]
+    fn rebase_literal_conflict() {
+        let old = Rope::from(format!("{}\nhello world\n", SMALL_ONE));
+        let new = Rope::from(format!("{}\nhello wor1d\n", SMALL_ONE));
+        let world = String::from(&old).find("world").unwrap();
+        let mut builder = Builder::new(old.len());
+        builder.replace(Interval::new(world, world + 5), Rope::from("there"));
+        let edit = builder.build();
+        assert!(edit.try_rebase_literal(&old, &new).is_none());
+
+        // an insertion right where the reload changed text is also a conflict
+        let insert =
+            Delta::simple_edit(Interval::new(world + 3, world + 3), Rope::from("!"), old.len());
+        assert!(insert.try_rebase_literal(&old, &new).is_none());
+    }
+
+    #[test]
+    fn streaming_invalid_utf8() {
+        let bad: &[u8] = &[b'a', 0xff, b'\n'];
+        assert!(LineHashDiff::compute_delta_streaming(bad, b"a\n".as_ref()).is_err());
+    }
+
+    /// A hasher that sends every line to the same bucket.
+    #[derive(Default)]
+    struct CollidingHasher;
+
+    impl Hasher for CollidingHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    #[test]
+    fn colliding_hasher_still_exact() {
+        let base = Rope::from(INTERVAL_STR);
+        let target_str = INTERVAL_STR.replace("interval", "span").replace("start", "first");
+        let target = Rope::from(&target_str);
+
+        let colliding = BuildHasherDefault::<CollidingHasher>::default();
+        let delta = LineHashDiff::compute_delta_with_hasher(&base, &target, colliding.clone());
+        assert_eq!(String::from(delta.apply(&base)), target_str);
+
+        let default_delta = LineHashDiff::compute_delta(&base, &target);
+        assert_eq!(format!("{:?}", delta), format!("{:?}", default_delta));
+
+        let small_one = Rope::from(SMALL_ONE);
+        let small_two = Rope::from(SMALL_TWO);
+        let delta = LineHashDiff::compute_delta_with_hasher(&small_one, &small_two, colliding);
+        assert_eq!(String::from(delta.apply(&small_one)), SMALL_TWO);
+
+        let seeded = RandomState::new();
+        let delta = LineHashDiff::compute_delta_with_hasher(&small_one, &small_two, seeded);
+        assert_eq!(String::from(delta.apply(&small_one)), SMALL_TWO);
+    }
+
+    #[test]
+    fn render_unified() {
+        let base = Rope::from("one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n");
+        let mut builder = Builder::new(base.len());
+        // rewrite part of "two", delete "four" and insert a line before "eight"
+        builder.replace(Interval::new(4, 7), Rope::from("TWO"));
+        builder.delete(Interval::new(14, 19));
+        builder.replace(Interval::new(34, 34), Rope::from("seven and a half\n"));
+        let delta = builder.build();
+
+        let rendered = delta.render_unified(&base, 1);
+        assert_eq!(
+            rendered,
+            "@@ -1,5 +1,4 @@\n one\n-two\n+TWO\n three\n-four\n five\n\
+             @@ -7,2 +6,3 @@\n seven\n+seven and a half\n eight\n"
+        );
+
+        // with more context the two hunks merge
+        let rendered = delta.render_unified(&base, 2);
+        assert!(rendered.starts_with("@@ -1,8 +1,8 @@\n one\n-two\n+TWO\n"));
+        assert_eq!(rendered.matches("@@").count(), 2);
+        assert!(rendered.contains("\n six\n seven\n+seven and a half\n eight\n"));
+
+        let identity = Builder::new(base.len()).build();
+        assert_eq!(identity.render_unified(&base, 3), "");
+    }
+
+    #[test]
+    fn apply_to_writer_matches_apply() {
+        let base = Rope::from(format!("{}{}", SMALL_ONE, "x".repeat(3000)));
+        let mut builder = Builder::new(base.len());
+        builder.replace(Interval::new(5, 40), Rope::from("ünïcode"));
+        builder.delete(Interval::new(100, 1200));
+        builder.replace(Interval::new(2500, 2500), Rope::from("y".repeat(2000)));
+        let delta = builder.build();
+
+        let mut out = Vec::new();
+        delta.apply_to_writer(&base, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), String::from(&delta.apply(&base)));
+    }
+
+    #[test]
+    #[should_panic(expected = "differs from expected at byte 9")]
+    fn assert_applies_reports_offset() {
+        let base = Rope::from("one two three");
+        let delta = Delta::simple_edit(Interval::new(4, 7), Rope::from("2"), base.len());
+        assert_applies(&delta, &base, &Rope::from("one 2 three"));
+        assert_applies(&delta, &base, &Rope::from("one 2 thrEE"));
+    }
+
+    #[test]
+    fn budgeted_diff_falls_back_to_coarse_delta() {
+        // every third line differs, so a full diff probes and copies line
+        // by line
+        let line = |i: usize, changed: bool| {
+            let verb = if changed { "changed" } else { "unchanged" };
+            format!("a line long enough to be hashed, {} number {}\n", verb, i)
+        };
+        let base = Rope::from((0..2000).map(|i| line(i, false)).collect::<String>());
+        let target = Rope::from((0..2000).map(|i| line(i, i % 3 == 1)).collect::<String>());
+
+        let coarse = LineHashDiff::compute_delta_budgeted(&base, &target, 10);
+        assert_applies(&coarse, &base, &target);
+        // at most the common prefix, one insert and the common suffix
+        assert!(coarse.els.len() <= 3);
+        assert!(coarse.inserts_len() > target.len() - 200);
+
+        let full = LineHashDiff::compute_delta_budgeted(&base, &target, usize::MAX);
+        assert_applies(&full, &base, &target);
+        assert!(full.inserts_len() < target.len() / 2);
+
+        // a budget covering every line of both documents gives the full result
+        let enough = LineHashDiff::compute_delta_budgeted(&base, &target, 4000);
+        assert_eq!(enough.inserts_len(), full.inserts_len());
+        let short = LineHashDiff::compute_delta_budgeted(&base, &target, 3000);
+        assert_eq!(short.inserts_len(), coarse.inserts_len());
+
+        // the unchanged prefix and suffix are still copied
+        let mut edited = String::from(&base);
+        edited.replace_range(50..60, &String::from(&target));
+        let edited = Rope::from(edited);
+        let delta = LineHashDiff::compute_delta_budgeted(&base, &edited, 0);
+        assert_applies(&delta, &base, &edited);
+        assert_eq!(delta.els.len(), 3);
+    }
+
+    #[test]
+    fn split_delta_by_lines() {
+        let base = Rope::from("one\ntwo\nthree\nfour\n");
+        let mut builder = Builder::new(base.len());
+        builder.replace(4..7, Rope::from("TWO"));
+        builder.replace(13..13, Rope::from("!"));
+        let delta = builder.build();
+
+        let lines = delta.split_by_lines(&base);
+        assert_eq!(lines.iter().map(|&(line, _)| line).collect::<Vec<_>>(), vec![1, 2]);
+        let new_text = String::from(delta.apply(&base));
+        let new_lines = new_text.split_inclusive('\n').collect::<Vec<_>>();
+        for (line, line_delta) in &lines {
+            let old = base.slice(base.offset_of_line(*line)..base.offset_of_line(line + 1));
+            assert_eq!(String::from(line_delta.apply(&old)), new_lines[*line]);
+        }
+
+        // joining two lines deletes the line ending from the first, and the
+        // rest from the second; an insert at a line start goes to that line
+        let mut builder = Builder::new(base.len());
+        builder.delete(2..5);
+        builder.replace(14..14, Rope::from("> "));
+        let lines = delta_lines(&builder.build(), &base);
+        assert_eq!(
+            lines,
+            vec![(0, "on".to_owned()), (1, "wo\n".to_owned()), (3, "> four\n".to_owned())]
+        );
+
+        // inserting at the end of a document ending in a newline
+        let mut builder = Builder::new(base.len());
+        builder.replace(19..19, Rope::from("five"));
+        assert_eq!(delta_lines(&builder.build(), &base), vec![(4, "five".to_owned())]);
+        assert!(RopeDelta::simple_edit(Interval::new(0, 0), Rope::from(""), base.len())
+            .split_by_lines(&base)
+            .is_empty());
+    }
+
+    fn delta_lines(delta: &RopeDelta, base: &Rope) -> Vec<(usize, String)> {
+        let last = base.measure::<LinesMetric>();
+        delta
+            .split_by_lines(base)
+            .into_iter()
+            .map(|(line, d)| {
+                let end = if line < last { base.offset_of_line(line + 1) } else { base.len() };
+                (line, String::from(d.apply(&base.slice(base.offset_of_line(line)..end))))
+            })
+            .collect()
     }
 }
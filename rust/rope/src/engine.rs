@@ -30,7 +30,8 @@
 
 use std::borrow::Cow;
 use std::collections::hash_map::DefaultHasher;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
 
 use crate::delta::{Delta, InsertDelta};
 use crate::interval::Interval;
@@ -68,6 +69,8 @@ pub struct Engine {
     undone_groups: BTreeSet<usize>, // set of undo_group id's
     /// The revision history of the document
     revs: Vec<Revision>,
+    /// The number of revisions to keep, if bounded; see `set_history_limit`
+    history_limit: Option<usize>,
 }
 
 // The advantage of using a session ID over random numbers is that it can be
@@ -85,8 +88,9 @@ pub struct RevId {
     num: u32,
 }
 
-#[derive(Debug)]
-struct Revision {
+/// A single entry in the edit history of an `Engine`.
+#[derive(Debug, Clone)]
+pub struct Revision {
     /// This uniquely represents the identity of this revision and it stays
     /// the same even if it is rebased or merged between devices.
     rev_id: RevId,
@@ -264,6 +268,11 @@ impl RevId {
 }
 
 impl Revision {
+    /// The id of this revision.
+    pub fn rev_id(&self) -> RevId {
+        self.rev_id
+    }
+
     #[cfg_attr(not(feature = "serde"), allow(dead_code))]
     pub(crate) fn as_ref(&self) -> RevisionRef<'_> {
         RevisionRef {
@@ -343,6 +352,18 @@ impl Engine {
         engine
     }
 
+    /// Create an empty `Engine` whose new revisions are identified by
+    /// `session` rather than the default single-user session.
+    ///
+    /// Two engines created with the same session and given the same edits
+    /// produce identical revision ids, which makes their serialized form
+    /// reproducible.
+    pub fn new_with_session(session: SessionId) -> Engine {
+        let mut engine = Engine::empty();
+        engine.set_session_id(session);
+        engine
+    }
+
     pub fn empty() -> Engine {
         let deletes_from_union = Subset::new(0);
         let rev = Revision {
@@ -361,6 +382,7 @@ impl Engine {
             deletes_from_union,
             undone_groups: BTreeSet::new(),
             revs: vec![rev],
+            history_limit: None,
         }
     }
 
@@ -466,6 +488,59 @@ impl Engine {
         self.find_rev_token(rev).map(|rev_index| self.rev_content_for_index(rev_index))
     }
 
+    /// Get text of the revision with the given id, if it can be found.
+    ///
+    /// The text is reconstructed from the current union string using the
+    /// deletions that applied at that revision, so no deltas are replayed.
+    pub fn text_at(&self, rev: RevId) -> Option<Rope> {
+        self.find_rev(rev).map(|rev_index| self.rev_content_for_index(rev_index))
+    }
+
+    /// Returns the ranges of the current head contributed by each undo group,
+    /// that is, the text its edits inserted that is still present.
+    ///
+    /// Ranges within a group are sorted and merged when adjacent. Groups that
+    /// contribute no text, such as undone groups or groups that only deleted,
+    /// are omitted.
+    pub fn undo_group_ranges(&self) -> BTreeMap<usize, Vec<Range<usize>>> {
+        let mut groups: BTreeMap<usize, Vec<Range<usize>>> = BTreeMap::new();
+        // the characters inserted by revisions after the current one, in the
+        // coordinates of the current union string
+        let mut later_inserts = Subset::new(self.deletes_from_union.len());
+        for rev in self.revs.iter().rev() {
+            if let Edit { ref inserts, undo_group, .. } = rev.edit {
+                let inserts = inserts.transform_expand(&later_inserts);
+                let in_head = inserts.transform_shrink(&self.deletes_from_union);
+                let mut ranges = in_head.range_iter(CountMatcher::NonZero).peekable();
+                if ranges.peek().is_some() {
+                    groups.entry(undo_group).or_default().extend(ranges.map(|(b, e)| b..e));
+                }
+                later_inserts = later_inserts.union(&inserts);
+            }
+        }
+        for ranges in groups.values_mut() {
+            ranges.sort_by_key(|r| r.start);
+            ranges.dedup_by(|next, prev| {
+                let adjacent = next.start == prev.end;
+                if adjacent {
+                    prev.end = next.end;
+                }
+                adjacent
+            });
+        }
+        groups
+    }
+
+    /// Returns the revisions created after `after`, oldest first, for
+    /// exporting history incrementally.
+    ///
+    /// The result is empty if `after` is the head revision, and an error if
+    /// `after` can't be found.
+    pub fn revisions_since(&self, after: RevId) -> Result<Vec<Revision>, Error> {
+        let ix = self.find_rev(after).ok_or_else(|| Error::MissingRevision(after.token()))?;
+        Ok(self.revs[ix + 1..].to_vec())
+    }
+
     /// A delta that, when applied to `base_rev`, results in the current head. Returns
     /// an error if there is not at least one edit.
     pub fn try_delta_rev_head(&self, base_rev: RevToken) -> Result<Delta<RopeInfo, String>, Error> {
@@ -606,6 +681,7 @@ impl Engine {
         self.text = new_text;
         self.tombstones = new_tombstones;
         self.deletes_from_union = new_deletes_from_union;
+        self.enforce_history_limit();
         Ok(())
     }
 
@@ -693,6 +769,30 @@ impl Engine {
         self.undone_groups = groups;
         self.revs.push(new_rev);
         self.rev_id_counter += 1;
+        self.enforce_history_limit();
+    }
+
+    /// Returns the delta that [`undo`](#method.undo) with the same `groups`
+    /// would apply to the current head, without performing the undo.
+    ///
+    /// This lets a consumer holding a copy of the text mirror an undo.
+    pub fn undo_as_delta(&self, groups: &BTreeSet<usize>) -> Delta<RopeInfo, String> {
+        let (_, new_deletes_from_union) = self.compute_undo(groups);
+        Delta::synthesize(&self.tombstones, &self.deletes_from_union, &new_deletes_from_union)
+    }
+
+    /// Returns `true` if `self` and `other` are in the same observable state:
+    /// the same text and tombstones, the same deletions from the union
+    /// string, and the same undone groups.
+    ///
+    /// Session ids and revision histories are not compared, so two engines
+    /// that converged by merging compare equivalent even though their
+    /// revisions were created by different sessions.
+    pub fn is_equivalent_to(&self, other: &Engine) -> bool {
+        self.text == other.text
+            && self.tombstones == other.tombstones
+            && self.deletes_from_union == other.deletes_from_union
+            && self.undone_groups == other.undone_groups
     }
 
     pub fn is_equivalent_revision(&self, base_rev: RevId, other_rev: RevId) -> bool {
@@ -706,6 +806,20 @@ impl Engine {
         base_subset.is_some() && base_subset == other_subset
     }
 
+    /// Returns the union string positions whose deleted/visible state
+    /// differs between revisions `a` and `b`, or `None` if either revision
+    /// is unknown.
+    ///
+    /// Both revisions' deletes-from-union sets are expressed against the
+    /// current union string before being xored, so text inserted after the
+    /// earlier revision shows up as flipped when it is visible in the later
+    /// one.
+    pub fn deletes_delta(&self, a: RevId, b: RevId) -> Option<Subset> {
+        let a_deletes = self.deletes_from_cur_union_for_index(self.find_rev(a)?);
+        let b_deletes = self.deletes_from_cur_union_for_index(self.find_rev(b)?);
+        Some(a_deletes.bitxor(&b_deletes))
+    }
+
     // Note: this function would need some work to handle retaining arbitrary revisions,
     // partly because the reachability calculation would become more complicated (a
     // revision might hold content from an undo group that would otherwise be gc'ed),
@@ -798,6 +912,63 @@ impl Engine {
         self.revs.reverse();
     }
 
+    /// Bounds the revision history to about `max_revs` revisions, trading
+    /// undo depth for memory in long sessions.
+    ///
+    /// Whenever an edit or undo takes the history past the limit, the undo
+    /// groups of the oldest revisions are passed to [`gc`](#method.gc),
+    /// which folds them into the current state. Those groups can no longer
+    /// be undone (or redone, if they were undone), revisions removed from
+    /// the history can no longer be used as the base of an edit, and, as
+    /// with any `gc`, merging with a peer that still expects them is
+    /// unsupported. The undo group of the head revision is never collected,
+    /// so the history may stay above a very small limit.
+    ///
+    /// [`merge`](#method.merge) does not enforce the limit, since collecting
+    /// revisions just received from a peer would break later merges with it,
+    /// so the history can exceed the limit after a merge until the next edit
+    /// or undo.
+    pub fn set_history_limit(&mut self, max_revs: usize) {
+        self.history_limit = Some(max_revs);
+        self.enforce_history_limit();
+    }
+
+    fn enforce_history_limit(&mut self) {
+        let limit = match self.history_limit {
+            Some(limit) if self.revs.len() > limit => limit,
+            _ => return,
+        };
+        let (head, older) = self.revs.split_last().unwrap();
+        let head_group = match head.edit {
+            Edit { undo_group, .. } => Some(undo_group),
+            Undo { .. } => None,
+        };
+        // gc drops every undo revision but the head, and every edit
+        // revision in a collected group
+        let mut removed = 0;
+        let mut group_sizes = BTreeMap::new();
+        for rev in older {
+            match rev.edit {
+                Edit { undo_group, .. } => *group_sizes.entry(undo_group).or_insert(0) += 1,
+                Undo { .. } => removed += 1,
+            }
+        }
+        let mut gc_groups = BTreeSet::new();
+        for rev in older {
+            if self.revs.len() - removed <= limit {
+                break;
+            }
+            if let Edit { undo_group, .. } = rev.edit {
+                if Some(undo_group) != head_group && gc_groups.insert(undo_group) {
+                    removed += group_sizes[&undo_group];
+                }
+            }
+        }
+        if removed > 0 {
+            self.gc(&gc_groups);
+        }
+    }
+
     /// Merge the new content from another Engine into this one with a CRDT merge
     pub fn merge(&mut self, other: &Engine) {
         let (mut new_revs, text, tombstones, deletes_from_union) = {
@@ -845,6 +1016,60 @@ impl Engine {
         );
         self.session = session;
     }
+
+    /// Applies the edits made by `f` as a single revision, so that they are
+    /// undone and redone in one step.
+    ///
+    /// Each edit passed to [`TransactionCtx::edit`] is based on the text as
+    /// left by the edits before it, available from [`TransactionCtx::text`].
+    /// Nothing is committed if `f` makes no changes.
+    ///
+    /// [`TransactionCtx::edit`]: struct.TransactionCtx.html#method.edit
+    /// [`TransactionCtx::text`]: struct.TransactionCtx.html#method.text
+    pub fn transaction(
+        &mut self,
+        priority: usize,
+        undo_group: usize,
+        f: impl FnOnce(&mut TransactionCtx),
+    ) {
+        let mut ctx = TransactionCtx { text: self.text.clone(), delta: None };
+        f(&mut ctx);
+        if let Some(delta) = ctx.delta.filter(|d| !d.is_identity()) {
+            let head = self.get_head_rev_id().token();
+            self.edit_rev(priority, undo_group, head, delta);
+        }
+    }
+}
+
+/// Collects the edits of an [`Engine::transaction`].
+///
+/// [`Engine::transaction`]: struct.Engine.html#method.transaction
+pub struct TransactionCtx {
+    /// The text with every edit so far applied.
+    text: Rope,
+    /// The composition of every edit so far, based on the engine's head.
+    delta: Option<Delta<RopeInfo, String>>,
+}
+
+impl TransactionCtx {
+    /// The text as left by the edits made so far in this transaction.
+    pub fn text(&self) -> &Rope {
+        &self.text
+    }
+
+    /// Adds an edit to the transaction.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `delta` is not based on the current [`text`](#method.text).
+    pub fn edit(&mut self, delta: Delta<RopeInfo, String>) {
+        assert_eq!(delta.base_len, self.text.len(), "delta is not based on the transaction text");
+        self.text = delta.apply(&self.text);
+        self.delta = Some(match self.delta.take() {
+            Some(prev) => prev.compose(&delta),
+            None => delta,
+        });
+    }
 }
 
 impl Engine {
@@ -910,6 +1135,7 @@ impl Engine {
             deletes_from_union,
             undone_groups,
             revs,
+            history_limit: None,
         }
     }
 }
@@ -1235,6 +1461,148 @@ mod tests {
         assert_eq!(json, ENGINE_FIXTURE.json);
     }
 
+    #[test]
+    fn transaction_undoes_in_one_step() {
+        let mut engine = Engine::new(Rope::from("select me"));
+        let revs_before = engine.revs.len();
+        engine.transaction(0, 1, |ctx| {
+            let open = Delta::simple_edit(Interval::new(0, 0), Rope::from("("), ctx.text().len());
+            ctx.edit(open);
+            // the second edit sees the first one's insert
+            let close = Delta::simple_edit(Interval::new(10, 10), Rope::from(")"), ctx.text().len());
+            ctx.edit(close);
+        });
+        assert_eq!("(select me)", String::from(engine.get_head()));
+        assert_eq!(engine.revs.len(), revs_before + 1);
+
+        engine.undo([1].iter().cloned().collect());
+        assert_eq!("select me", String::from(engine.get_head()));
+        engine.undo(BTreeSet::new());
+        assert_eq!("(select me)", String::from(engine.get_head()));
+
+        let revs_before = engine.revs.len();
+        engine.transaction(0, 2, |_| ());
+        assert_eq!(engine.revs.len(), revs_before);
+    }
+
+    #[test]
+    fn revisions_since() {
+        let mut engine = Engine::new(Rope::from("Hi"));
+        let first_rev = engine.get_head_rev_id();
+        engine.edit_rev(1, 1, first_rev.token(), Delta::simple_edit(Interval::new(2, 2), Rope::from("!"), 2));
+        let second_rev = engine.get_head_rev_id();
+        engine.edit_rev(1, 2, second_rev.token(), Delta::simple_edit(Interval::new(0, 0), Rope::from(">"), 3));
+        let third_rev = engine.get_head_rev_id();
+
+        let since_first = engine.revisions_since(first_rev).unwrap();
+        let ids = since_first.iter().map(Revision::rev_id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![second_rev, third_rev]);
+        assert!(engine.revisions_since(third_rev).unwrap().is_empty());
+
+        let unknown = RevId { session1: 7, session2: 7, num: 7 };
+        assert!(engine.revisions_since(unknown).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn revisions_since_round_trip() {
+        let mut engine = Engine::new(Rope::from("Hi"));
+        let first_rev = engine.get_head_rev_id();
+        engine.edit_rev(1, 1, first_rev.token(), Delta::simple_edit(Interval::new(2, 2), Rope::from("!"), 2));
+        let second_rev = engine.get_head_rev_id();
+        engine.edit_rev(1, 2, second_rev.token(), Delta::simple_edit(Interval::new(0, 0), Rope::from(">"), 3));
+
+        let since_first = engine.revisions_since(first_rev).unwrap();
+        let json = serde_json::to_string(&since_first).expect("serialize revisions");
+        let restored: Vec<Revision> = serde_json::from_str(&json).expect("deserialize revisions");
+        assert_eq!(restored.len(), 2);
+        assert_eq!(json, serde_json::to_string(&restored).unwrap());
+    }
+
+    #[test]
+    fn deletes_delta_undo() {
+        let mut engine = Engine::new(Rope::from(TEST_STR));
+        let first_rev = engine.get_head_rev_id();
+        let d = Delta::simple_edit(Interval::new(10, 20), Rope::from(""), TEST_STR.len());
+        engine.edit_rev(0, 1, first_rev.token(), d);
+        let deleted_rev = engine.get_head_rev_id();
+
+        let mut undo_groups = BTreeSet::new();
+        undo_groups.insert(1);
+        engine.undo(undo_groups);
+        let undone_rev = engine.get_head_rev_id();
+
+        let flipped = engine.deletes_delta(deleted_rev, undone_rev).unwrap();
+        assert_eq!(TEST_STR.len(), flipped.len());
+        assert_eq!(vec![(10, 20)], flipped.range_iter(CountMatcher::NonZero).collect::<Vec<_>>());
+        assert!(engine.deletes_delta(first_rev, undone_rev).unwrap().is_empty());
+        assert_eq!(Some(flipped), engine.deletes_delta(first_rev, deleted_rev));
+
+        let unknown = RevId { session1: 7, session2: 7, num: 7 };
+        assert!(engine.deletes_delta(first_rev, unknown).is_none());
+    }
+
+    #[test]
+    fn text_at_revisions() {
+        let mut engine = Engine::new(Rope::from("Hi"));
+        let first_rev = engine.get_head_rev_id();
+        let greet_delta = Delta::simple_edit(Interval::new(2, 2), Rope::from(" there"), 2);
+        engine.edit_rev(1, 1, first_rev.token(), greet_delta);
+
+        let second_rev = engine.get_head_rev_id();
+        let prefix_delta = Delta::simple_edit(Interval::new(0, 0), Rope::from("Well, "), engine.get_head().len());
+        engine.edit_rev(0, 2, second_rev.token(), prefix_delta);
+        let third_rev = engine.get_head_rev_id();
+
+        let mut undo_groups = BTreeSet::new();
+        undo_groups.insert(2);
+        engine.undo(undo_groups);
+
+        let head = engine.get_head_rev_id();
+        assert_eq!(engine.text_at(head).as_ref(), Some(engine.text_snapshot()));
+        assert_eq!("Hi there", String::from(engine.text_at(head).unwrap()));
+        assert_eq!("Hi", String::from(engine.text_at(first_rev).unwrap()));
+        assert_eq!("Hi there", String::from(engine.text_at(second_rev).unwrap()));
+        assert_eq!("Well, Hi there", String::from(engine.text_at(third_rev).unwrap()));
+
+        let unknown = RevId { session1: 7, session2: 7, num: 7 };
+        assert!(engine.text_at(unknown).is_none());
+    }
+
+    #[test]
+    fn undo_group_ranges() {
+        let mut engine = Engine::new(Rope::from("hello world"));
+        let d1 = Delta::simple_edit(Interval::new(0, 0), Rope::from("AA"), 11);
+        engine.edit_rev(1, 1, engine.get_head_rev_id().token(), d1);
+        let d2 = Delta::simple_edit(Interval::new(13, 13), Rope::from("BB"), 13);
+        engine.edit_rev(1, 2, engine.get_head_rev_id().token(), d2);
+        let d3 = Delta::simple_edit(Interval::new(2, 2), Rope::from("A"), 15);
+        engine.edit_rev(1, 1, engine.get_head_rev_id().token(), d3);
+        assert_eq!("AAAhello worldBB", String::from(engine.get_head()));
+
+        let ranges = engine.undo_group_ranges();
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[&0], vec![3..14]);
+        assert_eq!(ranges[&1], vec![0..3]);
+        assert_eq!(ranges[&2], vec![14..16]);
+
+        // deleting the middle of the base text splits its range
+        let d4 = Delta::simple_edit(Interval::new(8, 9), Rope::from("X"), 16);
+        engine.edit_rev(1, 3, engine.get_head_rev_id().token(), d4);
+        assert_eq!("AAAhelloXworldBB", String::from(engine.get_head()));
+        let ranges = engine.undo_group_ranges();
+        assert_eq!(ranges[&0], vec![3..8, 9..14]);
+        assert_eq!(ranges[&3], vec![8..9]);
+
+        let mut undo_groups = BTreeSet::new();
+        undo_groups.insert(1);
+        engine.undo(undo_groups);
+        let ranges = engine.undo_group_ranges();
+        assert!(!ranges.contains_key(&1));
+        assert_eq!(ranges[&0], vec![0..5, 6..11]);
+        assert_eq!(ranges[&2], vec![11..13]);
+    }
+
     #[test]
     fn edit_rev_empty() {
         let mut engine = Engine::new(Rope::from(TEST_STR));
@@ -1933,6 +2301,24 @@ mod tests {
         MergeTestState::new(2).run_script(&script[..]);
     }
 
+    #[test]
+    fn merge_disjoint_edits_converge() {
+        use self::MergeTestOp::*;
+        let script = vec![
+            Edit { ei: 0, p: 1, u: 1, d: parse_delta("abcdef") },
+            Merge(1,0),
+            AssertAll("abcdef".to_owned()),
+            Edit { ei: 0, p: 1, u: 2, d: parse_delta("!X-----") },
+            Assert(0, "Xbcdef".to_owned()),
+            Edit { ei: 1, p: 3, u: 2, d: parse_delta("-----!Y") },
+            Assert(1, "abcdeY".to_owned()),
+            Merge(0,1),
+            Merge(1,0),
+            AssertAll("XbcdeY".to_owned()),
+        ];
+        MergeTestState::new(2).run_script(&script[..]);
+    }
+
     /// I have a scanned whiteboard diagram of doing this merge by hand, good for reference
     #[test]
     fn merge_whiteboard() {
@@ -2014,4 +2400,126 @@ mod tests {
         ];
         MergeTestState::new(3).run_script(&script[..]);
     }
+
+    /// Replaces `iv` in the head text with `text`, as an edit in undo group `group`.
+    fn edit_head(engine: &mut Engine, group: usize, iv: Interval, text: &str) {
+        let head = engine.get_head_rev_id().token();
+        let delta = Delta::simple_edit(iv, Rope::from(text), engine.get_head().len());
+        engine.edit_rev(1, group, head, delta);
+    }
+
+    #[test]
+    fn new_with_session_ids() {
+        let mut engine = Engine::new_with_session((42, 7));
+        edit_head(&mut engine, 1, Interval::new(0, 0), "a");
+        assert_eq!(engine.get_head_rev_id().session_id(), (42, 7));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn same_session_serializes_identically() {
+        fn build() -> Engine {
+            let mut engine = Engine::new_with_session((0xfeed, 3));
+            let edits = [(0, "hello"), (5, " world"), (0, ">> ")];
+            for (group, &(at, text)) in edits.iter().enumerate() {
+                edit_head(&mut engine, group + 1, Interval::new(at, at), text);
+            }
+            engine.undo([2].iter().cloned().collect());
+            engine
+        }
+        let a = serde_json::to_string(&build()).expect("serialize engine");
+        let b = serde_json::to_string(&build()).expect("serialize engine");
+        assert_eq!(a, b);
+        assert!(a.contains(&0xfeed.to_string()));
+    }
+
+    #[test]
+    fn undo_as_delta_matches_undo() {
+        fn build() -> Engine {
+            let mut engine = Engine::new(Rope::from(TEST_STR));
+            edit_head(&mut engine, 1, Interval::new(0, 10), "fresh ");
+            edit_head(&mut engine, 2, Interval::new(20, 25), "");
+            engine
+        }
+
+        for groups in [vec![1], vec![2], vec![1, 2], vec![]] {
+            let groups: BTreeSet<usize> = groups.into_iter().collect();
+            let mut engine = build();
+            let head = engine.get_head().clone();
+            let revs = engine.revs.len();
+            let delta = engine.undo_as_delta(&groups);
+            assert_eq!(engine.revs.len(), revs);
+            engine.undo(groups);
+            assert_eq!(String::from(delta.apply(&head)), String::from(engine.get_head()));
+        }
+
+        // relative to a state that already has an undo applied
+        let mut engine = build();
+        engine.undo([1].iter().cloned().collect());
+        let groups: BTreeSet<usize> = [2].iter().cloned().collect();
+        let delta = engine.undo_as_delta(&groups);
+        let head = engine.get_head().clone();
+        engine.undo(groups);
+        assert_eq!(String::from(delta.apply(&head)), String::from(engine.get_head()));
+    }
+
+    #[test]
+    fn is_equivalent_to_ignores_sessions() {
+        let edits = [(0, 0, "hello"), (5, 5, " world"), (0, 1, "J"), (6, 11, "there")];
+        let mut a = Engine::new_with_session((1, 1));
+        let mut b = Engine::new_with_session((2, 2));
+        for (group, &(start, end, text)) in edits.iter().enumerate() {
+            for engine in [&mut a, &mut b] {
+                edit_head(engine, group, Interval::new(start, end), text);
+            }
+        }
+        assert_ne!(a.get_head_rev_id(), b.get_head_rev_id());
+        assert!(a.is_equivalent_to(&b));
+
+        a.undo([2].iter().cloned().collect());
+        assert!(!a.is_equivalent_to(&b));
+        b.undo([2].iter().cloned().collect());
+        assert!(a.is_equivalent_to(&b));
+
+        // concurrent edits converging through merge
+        let mut c = Engine::new(Rope::from("base"));
+        let mut d = Engine::new_with_session((4, 4));
+        d.merge(&c);
+        assert!(c.is_equivalent_to(&d));
+        edit_head(&mut c, 1, Interval::new(0, 0), ">");
+        edit_head(&mut d, 1, Interval::new(4, 4), "<");
+        assert!(!c.is_equivalent_to(&d));
+        c.merge(&d);
+        d.merge(&c);
+        assert_eq!(String::from(c.get_head()), ">base<");
+        assert!(c.is_equivalent_to(&d));
+    }
+
+    #[test]
+    fn history_limit_folds_old_revisions() {
+        let mut engine = Engine::new(Rope::from(TEST_STR));
+        let mut unlimited = Engine::new(Rope::from(TEST_STR));
+        engine.set_history_limit(4);
+        assert_eq!(engine.revs.len(), 2);
+        let mut expected = String::from(TEST_STR);
+        for i in 0..20 {
+            let text = ((b'a' + i as u8) as char).to_string();
+            expected.insert_str(i, &text);
+            for e in [&mut engine, &mut unlimited] {
+                edit_head(e, i + 1, Interval::new(i, i), &text);
+            }
+            assert!(engine.revs.len() <= 4);
+            assert_eq!(String::from(engine.get_head()), expected);
+        }
+        assert_eq!(unlimited.revs.len(), 22);
+
+        // recent groups can still be undone, and the limit still holds
+        let undo: BTreeSet<usize> = [19, 20].iter().cloned().collect();
+        engine.undo(undo.clone());
+        unlimited.undo(undo);
+        assert!(engine.revs.len() <= 4);
+        assert_eq!(String::from(engine.get_head()), String::from(unlimited.get_head()));
+        assert_eq!(&String::from(engine.get_head())[..18], &expected[..18]);
+        assert_eq!(&String::from(engine.get_head())[18..], TEST_STR);
+    }
 }
@@ -14,16 +14,19 @@
 
 //! Implementation of string finding in ropes.
 
-use std::cmp::min;
+use std::cmp::{max, min};
 
 use memchr::{memchr, memchr2, memchr3};
 
+use crate::interval::Interval;
 use crate::rope::BaseMetric;
 use crate::rope::LinesRaw;
-use crate::rope::RopeInfo;
+use crate::rope::{Rope, RopeInfo};
 use crate::tree::Cursor;
 use regex::Regex;
+use smallvec::SmallVec;
 use std::borrow::Cow;
+use std::ops::Range;
 use std::str;
 
 /// The result of a [`find`][find] operation.
@@ -46,9 +49,9 @@ pub enum FindResult {
 pub enum CaseMatching {
     /// Require an exact codepoint-for-codepoint match (implies case sensitivity).
     Exact,
-    /// Case insensitive match. Guaranteed to work for the ASCII case, and
-    /// reasonably well otherwise (it is currently defined in terms of the
-    /// `to_lowercase` methods in the Rust standard library).
+    /// Case insensitive match, comparing the full Unicode case folding of
+    /// both strings (the default, non-Turkic folding). A single character may
+    /// fold to several, so for example "STRASSE" matches "straße".
     CaseInsensitive,
 }
 
@@ -76,6 +79,268 @@ pub fn find(
     }
 }
 
+/// Finds every match of `pat` lying entirely within one of `intervals`, in
+/// order of position.
+///
+/// Each interval is searched on its own, as a slice of `rope`, so the text
+/// between intervals is never scanned, and a match crossing an interval
+/// boundary is not reported. Regular expression anchors apply at the edges
+/// of each interval. Matches found through overlapping intervals are only
+/// reported once.
+pub fn find_in_intervals(
+    rope: &Rope,
+    intervals: &[Interval],
+    pat: &str,
+    cm: CaseMatching,
+    regex: Option<&Regex>,
+) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    for iv in intervals {
+        let text = rope.slice(*iv);
+        let mut cursor = Cursor::new(&text, 0);
+        let mut lines = text.lines_raw(..);
+        while let Some(start) = find(&mut cursor, &mut lines, cm, pat, regex) {
+            let end = cursor.pos();
+            matches.push(iv.start() + start..iv.start() + end);
+            // step past an empty match so the search makes progress
+            if start == end && cursor.next::<BaseMetric>().is_none() {
+                break;
+            }
+            lines = text.lines_raw(cursor.pos()..);
+        }
+    }
+    matches.sort_by_key(|m| (m.start, m.end));
+    matches.dedup();
+    matches
+}
+
+/// Finds every match of `pat` in `rope`, reporting each with the line and
+/// column of its start, as from [`Rope::position_of_offset`].
+///
+/// Matches are found as by [`find_in_intervals`] over the whole document.
+///
+/// [`Rope::position_of_offset`]: ../rope/struct.Rope.html#method.position_of_offset
+/// [`find_in_intervals`]: fn.find_in_intervals.html
+pub fn find_positions(
+    rope: &Rope,
+    pat: &str,
+    cm: CaseMatching,
+    regex: Option<&Regex>,
+) -> Vec<(Range<usize>, usize, usize)> {
+    find_in_intervals(rope, &[Interval::new(0, rope.len())], pat, cm, regex)
+        .into_iter()
+        .map(|m| {
+            let (line, column) = rope.position_of_offset(m.start);
+            (m, line, column)
+        })
+        .collect()
+}
+
+/// Counts the lines of `rope` on which at least one match of `pat` starts.
+///
+/// The search is a single pass: once a match is found the rest of its line
+/// is skipped, so each line is counted once and no match ranges are
+/// collected. Searching resumes after a match that runs past its line.
+///
+/// Can panic if `pat` is empty.
+pub fn count_match_lines(rope: &Rope, pat: &str, cm: CaseMatching, regex: Option<&Regex>) -> usize {
+    let mut count = 0;
+    let mut cursor = Cursor::new(rope, 0);
+    let mut lines = rope.lines_raw(..);
+    while let Some(start) = find(&mut cursor, &mut lines, cm, pat, regex) {
+        count += 1;
+        let next_line = rope.offset_of_line(rope.line_of_offset(start) + 1);
+        let next = max(cursor.pos(), next_line);
+        if next >= rope.len() {
+            break;
+        }
+        cursor.set(next);
+        lines = rope.lines_raw(next..);
+    }
+    count
+}
+
+/// Finds the first match of `regex` that begins at or after `start`,
+/// returning its range in `rope`.
+///
+/// Unlike [`find`], which feeds the regex a line at a time unless the
+/// pattern is recognizably multi-line, this always matches against the
+/// document as one contiguous string, so patterns such as `foo[\s\S]*?bar`
+/// can match across any number of lines. Anchors and word boundaries see the
+/// text before `start` too. The cost is memory: a rope spanning more than one
+/// leaf is copied into a buffer of its full length for each call, where the
+/// line-buffered path only holds a line.
+///
+/// [`find`]: fn.find.html
+pub fn find_regex_multiline(rope: &Rope, regex: &Regex, start: usize) -> Option<Range<usize>> {
+    if start > rope.len() {
+        return None;
+    }
+    let text = rope.slice_to_cow(..);
+    regex.find_at(&text, start).map(|m| m.start()..m.end())
+}
+
+/// A set of literal patterns compiled into one automaton, so that a rope can
+/// be searched for all of them in a single pass.
+///
+/// Case-insensitive matching only folds ASCII letters, unlike
+/// [`CaseMatching::CaseInsensitive`] in [`find`].
+///
+/// [`CaseMatching::CaseInsensitive`]: enum.CaseMatching.html#variant.CaseInsensitive
+/// [`find`]: fn.find.html
+#[cfg(feature = "aho-corasick")]
+pub struct MultiFind {
+    dfa: aho_corasick::dfa::DFA,
+}
+
+#[cfg(feature = "aho-corasick")]
+impl MultiFind {
+    /// Compiles `patterns`, which are identified in matches by their index.
+    ///
+    /// Panics if a pattern is empty, or if the patterns are too large for the
+    /// automaton.
+    pub fn new(patterns: &[&str], cm: CaseMatching) -> MultiFind {
+        use aho_corasick::{dfa::DFA, MatchKind};
+
+        assert!(patterns.iter().all(|p| !p.is_empty()), "empty pattern");
+        let dfa = DFA::builder()
+            .match_kind(MatchKind::Standard)
+            .ascii_case_insensitive(cm == CaseMatching::CaseInsensitive)
+            .build(patterns)
+            .expect("patterns too large for automaton");
+        MultiFind { dfa }
+    }
+
+    /// Returns every occurrence of every pattern in `rope`, as the index of
+    /// the pattern and the range it matched, ordered by end offset.
+    ///
+    /// Overlapping occurrences are all reported, including occurrences of
+    /// one pattern inside another. The automaton is fed the rope a chunk at
+    /// a time and keeps its state from one chunk to the next, so matches
+    /// spanning leaves are found without copying the text.
+    pub fn find_all(&self, rope: &Rope) -> Vec<(usize, Range<usize>)> {
+        use aho_corasick::{automaton::Automaton, Anchored};
+
+        let dfa = &self.dfa;
+        let mut matches = Vec::new();
+        let mut state = dfa.start_state(Anchored::No).unwrap();
+        let mut offset = 0;
+        for chunk in rope.iter_chunks(..) {
+            for (i, &b) in chunk.as_bytes().iter().enumerate() {
+                state = dfa.next_state(Anchored::No, state, b);
+                if dfa.is_special(state) && dfa.is_match(state) {
+                    let end = offset + i + 1;
+                    for ix in 0..dfa.match_len(state) {
+                        let pattern = dfa.match_pattern(state, ix);
+                        matches.push((pattern.as_usize(), end - dfa.pattern_len(pattern)..end));
+                    }
+                }
+            }
+            offset += chunk.len();
+        }
+        matches
+    }
+}
+
+/// Updates the matches of a literal, case-sensitive search after the query
+/// changes from `prev_query` to `new_query`.
+///
+/// `prev_matches` must be the result of searching the whole of `rope` for
+/// `prev_query`, as from [`find_in_intervals`]. When `new_query` extends
+/// `prev_query`, every match of it begins with a match of `prev_query`, so
+/// only the previous match starts are checked against the longer query. The
+/// result is the same as searching `rope` for `new_query` afresh, which is
+/// what is done when `new_query` is not an extension, or when `prev_query`
+/// can overlap itself (in which case `prev_matches` may omit occurrences).
+///
+/// [`find_in_intervals`]: fn.find_in_intervals.html
+pub fn refine_matches(
+    rope: &Rope,
+    prev_query: &str,
+    prev_matches: &[Range<usize>],
+    new_query: &str,
+) -> Vec<Range<usize>> {
+    let mut verified = 0;
+    refine_from_previous(rope, prev_query, prev_matches, new_query, &mut verified).unwrap_or_else(
+        || {
+            find_in_intervals(
+                rope,
+                &[Interval::new(0, rope.len())],
+                new_query,
+                CaseMatching::Exact,
+                None,
+            )
+        },
+    )
+}
+
+/// The incremental path of [`refine_matches`], counting the positions it
+/// checks in `verified`. Returns `None` if a full search is needed.
+fn refine_from_previous(
+    rope: &Rope,
+    prev_query: &str,
+    prev_matches: &[Range<usize>],
+    new_query: &str,
+    verified: &mut usize,
+) -> Option<Vec<Range<usize>>> {
+    if prev_query.is_empty() || !new_query.starts_with(prev_query) || has_border(prev_query) {
+        return None;
+    }
+    let mut matches: Vec<Range<usize>> = Vec::new();
+    for m in prev_matches {
+        let end = m.start + new_query.len();
+        if matches.last().is_some_and(|last| m.start < last.end) {
+            continue;
+        }
+        *verified += 1;
+        if end <= rope.len() && rope.slice_to_cow(m.start..end) == new_query {
+            matches.push(m.start..end);
+        }
+    }
+    Some(matches)
+}
+
+/// Returns `true` if some proper, non-empty prefix of `s` is also a suffix,
+/// that is, if two occurrences of `s` can overlap.
+fn has_border(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    (1..bytes.len()).any(|n| bytes[..n] == bytes[bytes.len() - n..])
+}
+
+/// Finds the next match of `regex` starting at the current location of the
+/// cursor, returning the absolute byte range of each capture group. Index 0
+/// is the whole match; groups that did not participate are `None`.
+///
+/// As with [`find`][find], `lines` should start at the cursor position, and
+/// on success the cursor is updated to immediately follow the match.
+///
+/// [find]: fn.find.html
+pub fn find_captures(
+    cursor: &mut Cursor<RopeInfo, String>,
+    lines: &mut LinesRaw,
+    regex: &Regex,
+) -> Option<Vec<Option<Range<usize>>>> {
+    let multiline = is_multiline_regex(regex.as_str());
+    loop {
+        let orig_position = cursor.pos();
+        let text: Cow<str> = if multiline { Cow::Owned(lines.collect()) } else { lines.next()? };
+        if text.is_empty() {
+            return None;
+        }
+
+        // group offsets are relative to the start of the searched text
+        if let Some(caps) = regex.captures(&text) {
+            cursor.set(orig_position + caps.get(0).unwrap().end());
+            let groups = caps
+                .iter()
+                .map(|m| m.map(|m| orig_position + m.start()..orig_position + m.end()))
+                .collect();
+            return Some(groups);
+        }
+        cursor.set(orig_position + text.len());
+    }
+}
+
 /// A variant of [`find`][find] that makes a bounded amount of progress, then either
 /// returns or suspends (returning `TryAgain`).
 ///
@@ -118,10 +383,15 @@ pub fn find_progress(
                     find_progress_iter(cursor, lines, pat, scanner, matcher, num_steps)
                 }
                 CaseMatching::CaseInsensitive => {
-                    let pat_lower = pat.to_lowercase();
+                    let pat_lower = fold_case(pat);
                     let b = pat_lower.as_bytes()[0];
+                    let first = pat_lower.chars().next().unwrap();
                     let matcher = compare_cursor_str_casei;
-                    if b == b'i' {
+                    if SPECIAL_FOLDS.iter().any(|&(_, folded)| folded.starts_with(first)) {
+                        // some non-ASCII characters fold to a sequence starting with `first`
+                        let scanner = |s: &str| scan_folded(first, s);
+                        find_progress_iter(cursor, lines, &pat_lower, scanner, matcher, num_steps)
+                    } else if b == b'i' {
                         // 0xC4 is first utf-8 byte of 'İ'
                         let scanner = |s: &str| memchr3(b'i', b'I', 0xC4, s.as_bytes());
                         find_progress_iter(cursor, lines, &pat_lower, scanner, matcher, num_steps)
@@ -136,8 +406,7 @@ pub fn find_progress(
                         let scanner = |s: &str| memchr(b, s.as_bytes());
                         find_progress_iter(cursor, lines, &pat_lower, scanner, matcher, num_steps)
                     } else {
-                        let c = pat.chars().next().unwrap();
-                        let scanner = |s: &str| scan_lowercase(c, s);
+                        let scanner = |s: &str| scan_folded(first, s);
                         find_progress_iter(cursor, lines, &pat_lower, scanner, matcher, num_steps)
                     }
                 }
@@ -232,29 +501,39 @@ pub fn compare_cursor_str(
     None
 }
 
-/// Like `compare_cursor_str` but case invariant (using to_lowercase() to
+/// Like `compare_cursor_str` but case invariant (using [`fold_case`] to
 /// normalize both strings before comparison). Returns the start position
 /// of the match.
+///
+/// A match covers whole characters of the text: a pattern that ends partway
+/// through the folding of a character, such as `"s"` against `"ß"` (which
+/// folds to `"ss"`), does not match it.
 pub fn compare_cursor_str_casei(
     cursor: &mut Cursor<RopeInfo, String>,
     _lines: &mut LinesRaw,
     pat: &str,
 ) -> Option<usize> {
     let start_position = cursor.pos();
-    let mut pat_iter = pat.chars();
+    let mut pat_iter = pat.chars().flat_map(fold_char);
     let mut c = pat_iter.next().unwrap();
     loop {
         if let Some(rope_c) = cursor.next_codepoint() {
-            for lc_c in rope_c.to_lowercase() {
+            let folded = fold_char(rope_c);
+            for (i, &lc_c) in folded.iter().enumerate() {
                 if c != lc_c {
                     cursor.set(start_position);
                     cursor.next::<BaseMetric>();
                     return None;
                 }
-                if let Some(next_c) = pat_iter.next() {
-                    c = next_c;
-                } else {
-                    return Some(start_position);
+                match pat_iter.next() {
+                    Some(next_c) => c = next_c,
+                    None if i + 1 == folded.len() => return Some(start_position),
+                    None => {
+                        // the pattern ends inside the folding of `rope_c`
+                        cursor.set(start_position);
+                        cursor.next::<BaseMetric>();
+                        return None;
+                    }
                 }
             }
         } else {
@@ -320,13 +599,235 @@ pub fn is_multiline_regex(regex: &str) -> bool {
     // todo: currently multiline mode is ignored
     let multiline_indicators = [r"\n", r"\r", r"[[:space:]]"];
 
-    multiline_indicators.iter().any(|&i| regex.contains(i))
+    // a literal query escaped into a regex keeps its line breaks verbatim
+    regex.contains(['\n', '\r']) || multiline_indicators.iter().any(|&i| regex.contains(i))
+}
+
+/// The full case foldings (statuses `C` and `F` in the Unicode
+/// `CaseFolding.txt`) of lowercase characters that `char::to_lowercase`
+/// leaves alone, sorted by character. Every other character folds to its
+/// lowercase form, possibly followed by one of these.
+const SPECIAL_FOLDS: &[(char, &str)] = &[
+    ('\u{B5}', "\u{3BC}"),
+    ('\u{DF}', "ss"),
+    ('\u{149}', "\u{2BC}n"),
+    ('\u{17F}', "s"),
+    ('\u{1F0}', "j\u{30C}"),
+    ('\u{345}', "\u{3B9}"),
+    ('\u{390}', "\u{3B9}\u{308}\u{301}"),
+    ('\u{3B0}', "\u{3C5}\u{308}\u{301}"),
+    ('\u{3C2}', "\u{3C3}"),
+    ('\u{3D0}', "\u{3B2}"),
+    ('\u{3D1}', "\u{3B8}"),
+    ('\u{3D5}', "\u{3C6}"),
+    ('\u{3D6}', "\u{3C0}"),
+    ('\u{3F0}', "\u{3BA}"),
+    ('\u{3F1}', "\u{3C1}"),
+    ('\u{3F5}', "\u{3B5}"),
+    ('\u{587}', "\u{565}\u{582}"),
+    ('\u{13F8}', "\u{13F0}"),
+    ('\u{13F9}', "\u{13F1}"),
+    ('\u{13FA}', "\u{13F2}"),
+    ('\u{13FB}', "\u{13F3}"),
+    ('\u{13FC}', "\u{13F4}"),
+    ('\u{13FD}', "\u{13F5}"),
+    ('\u{1C80}', "\u{432}"),
+    ('\u{1C81}', "\u{434}"),
+    ('\u{1C82}', "\u{43E}"),
+    ('\u{1C83}', "\u{441}"),
+    ('\u{1C84}', "\u{442}"),
+    ('\u{1C85}', "\u{442}"),
+    ('\u{1C86}', "\u{44A}"),
+    ('\u{1C87}', "\u{463}"),
+    ('\u{1C88}', "\u{A64B}"),
+    ('\u{1E96}', "h\u{331}"),
+    ('\u{1E97}', "t\u{308}"),
+    ('\u{1E98}', "w\u{30A}"),
+    ('\u{1E99}', "y\u{30A}"),
+    ('\u{1E9A}', "a\u{2BE}"),
+    ('\u{1E9B}', "\u{1E61}"),
+    ('\u{1F50}', "\u{3C5}\u{313}"),
+    ('\u{1F52}', "\u{3C5}\u{313}\u{300}"),
+    ('\u{1F54}', "\u{3C5}\u{313}\u{301}"),
+    ('\u{1F56}', "\u{3C5}\u{313}\u{342}"),
+    ('\u{1F80}', "\u{1F00}\u{3B9}"),
+    ('\u{1F81}', "\u{1F01}\u{3B9}"),
+    ('\u{1F82}', "\u{1F02}\u{3B9}"),
+    ('\u{1F83}', "\u{1F03}\u{3B9}"),
+    ('\u{1F84}', "\u{1F04}\u{3B9}"),
+    ('\u{1F85}', "\u{1F05}\u{3B9}"),
+    ('\u{1F86}', "\u{1F06}\u{3B9}"),
+    ('\u{1F87}', "\u{1F07}\u{3B9}"),
+    ('\u{1F90}', "\u{1F20}\u{3B9}"),
+    ('\u{1F91}', "\u{1F21}\u{3B9}"),
+    ('\u{1F92}', "\u{1F22}\u{3B9}"),
+    ('\u{1F93}', "\u{1F23}\u{3B9}"),
+    ('\u{1F94}', "\u{1F24}\u{3B9}"),
+    ('\u{1F95}', "\u{1F25}\u{3B9}"),
+    ('\u{1F96}', "\u{1F26}\u{3B9}"),
+    ('\u{1F97}', "\u{1F27}\u{3B9}"),
+    ('\u{1FA0}', "\u{1F60}\u{3B9}"),
+    ('\u{1FA1}', "\u{1F61}\u{3B9}"),
+    ('\u{1FA2}', "\u{1F62}\u{3B9}"),
+    ('\u{1FA3}', "\u{1F63}\u{3B9}"),
+    ('\u{1FA4}', "\u{1F64}\u{3B9}"),
+    ('\u{1FA5}', "\u{1F65}\u{3B9}"),
+    ('\u{1FA6}', "\u{1F66}\u{3B9}"),
+    ('\u{1FA7}', "\u{1F67}\u{3B9}"),
+    ('\u{1FB2}', "\u{1F70}\u{3B9}"),
+    ('\u{1FB3}', "\u{3B1}\u{3B9}"),
+    ('\u{1FB4}', "\u{3AC}\u{3B9}"),
+    ('\u{1FB6}', "\u{3B1}\u{342}"),
+    ('\u{1FB7}', "\u{3B1}\u{342}\u{3B9}"),
+    ('\u{1FBE}', "\u{3B9}"),
+    ('\u{1FC2}', "\u{1F74}\u{3B9}"),
+    ('\u{1FC3}', "\u{3B7}\u{3B9}"),
+    ('\u{1FC4}', "\u{3AE}\u{3B9}"),
+    ('\u{1FC6}', "\u{3B7}\u{342}"),
+    ('\u{1FC7}', "\u{3B7}\u{342}\u{3B9}"),
+    ('\u{1FD2}', "\u{3B9}\u{308}\u{300}"),
+    ('\u{1FD3}', "\u{3B9}\u{308}\u{301}"),
+    ('\u{1FD6}', "\u{3B9}\u{342}"),
+    ('\u{1FD7}', "\u{3B9}\u{308}\u{342}"),
+    ('\u{1FE2}', "\u{3C5}\u{308}\u{300}"),
+    ('\u{1FE3}', "\u{3C5}\u{308}\u{301}"),
+    ('\u{1FE4}', "\u{3C1}\u{313}"),
+    ('\u{1FE6}', "\u{3C5}\u{342}"),
+    ('\u{1FE7}', "\u{3C5}\u{308}\u{342}"),
+    ('\u{1FF2}', "\u{1F7C}\u{3B9}"),
+    ('\u{1FF3}', "\u{3C9}\u{3B9}"),
+    ('\u{1FF4}', "\u{3CE}\u{3B9}"),
+    ('\u{1FF6}', "\u{3C9}\u{342}"),
+    ('\u{1FF7}', "\u{3C9}\u{342}\u{3B9}"),
+    ('\u{AB70}', "\u{13A0}"),
+    ('\u{AB71}', "\u{13A1}"),
+    ('\u{AB72}', "\u{13A2}"),
+    ('\u{AB73}', "\u{13A3}"),
+    ('\u{AB74}', "\u{13A4}"),
+    ('\u{AB75}', "\u{13A5}"),
+    ('\u{AB76}', "\u{13A6}"),
+    ('\u{AB77}', "\u{13A7}"),
+    ('\u{AB78}', "\u{13A8}"),
+    ('\u{AB79}', "\u{13A9}"),
+    ('\u{AB7A}', "\u{13AA}"),
+    ('\u{AB7B}', "\u{13AB}"),
+    ('\u{AB7C}', "\u{13AC}"),
+    ('\u{AB7D}', "\u{13AD}"),
+    ('\u{AB7E}', "\u{13AE}"),
+    ('\u{AB7F}', "\u{13AF}"),
+    ('\u{AB80}', "\u{13B0}"),
+    ('\u{AB81}', "\u{13B1}"),
+    ('\u{AB82}', "\u{13B2}"),
+    ('\u{AB83}', "\u{13B3}"),
+    ('\u{AB84}', "\u{13B4}"),
+    ('\u{AB85}', "\u{13B5}"),
+    ('\u{AB86}', "\u{13B6}"),
+    ('\u{AB87}', "\u{13B7}"),
+    ('\u{AB88}', "\u{13B8}"),
+    ('\u{AB89}', "\u{13B9}"),
+    ('\u{AB8A}', "\u{13BA}"),
+    ('\u{AB8B}', "\u{13BB}"),
+    ('\u{AB8C}', "\u{13BC}"),
+    ('\u{AB8D}', "\u{13BD}"),
+    ('\u{AB8E}', "\u{13BE}"),
+    ('\u{AB8F}', "\u{13BF}"),
+    ('\u{AB90}', "\u{13C0}"),
+    ('\u{AB91}', "\u{13C1}"),
+    ('\u{AB92}', "\u{13C2}"),
+    ('\u{AB93}', "\u{13C3}"),
+    ('\u{AB94}', "\u{13C4}"),
+    ('\u{AB95}', "\u{13C5}"),
+    ('\u{AB96}', "\u{13C6}"),
+    ('\u{AB97}', "\u{13C7}"),
+    ('\u{AB98}', "\u{13C8}"),
+    ('\u{AB99}', "\u{13C9}"),
+    ('\u{AB9A}', "\u{13CA}"),
+    ('\u{AB9B}', "\u{13CB}"),
+    ('\u{AB9C}', "\u{13CC}"),
+    ('\u{AB9D}', "\u{13CD}"),
+    ('\u{AB9E}', "\u{13CE}"),
+    ('\u{AB9F}', "\u{13CF}"),
+    ('\u{ABA0}', "\u{13D0}"),
+    ('\u{ABA1}', "\u{13D1}"),
+    ('\u{ABA2}', "\u{13D2}"),
+    ('\u{ABA3}', "\u{13D3}"),
+    ('\u{ABA4}', "\u{13D4}"),
+    ('\u{ABA5}', "\u{13D5}"),
+    ('\u{ABA6}', "\u{13D6}"),
+    ('\u{ABA7}', "\u{13D7}"),
+    ('\u{ABA8}', "\u{13D8}"),
+    ('\u{ABA9}', "\u{13D9}"),
+    ('\u{ABAA}', "\u{13DA}"),
+    ('\u{ABAB}', "\u{13DB}"),
+    ('\u{ABAC}', "\u{13DC}"),
+    ('\u{ABAD}', "\u{13DD}"),
+    ('\u{ABAE}', "\u{13DE}"),
+    ('\u{ABAF}', "\u{13DF}"),
+    ('\u{ABB0}', "\u{13E0}"),
+    ('\u{ABB1}', "\u{13E1}"),
+    ('\u{ABB2}', "\u{13E2}"),
+    ('\u{ABB3}', "\u{13E3}"),
+    ('\u{ABB4}', "\u{13E4}"),
+    ('\u{ABB5}', "\u{13E5}"),
+    ('\u{ABB6}', "\u{13E6}"),
+    ('\u{ABB7}', "\u{13E7}"),
+    ('\u{ABB8}', "\u{13E8}"),
+    ('\u{ABB9}', "\u{13E9}"),
+    ('\u{ABBA}', "\u{13EA}"),
+    ('\u{ABBB}', "\u{13EB}"),
+    ('\u{ABBC}', "\u{13EC}"),
+    ('\u{ABBD}', "\u{13ED}"),
+    ('\u{ABBE}', "\u{13EE}"),
+    ('\u{ABBF}', "\u{13EF}"),
+    ('\u{FB00}', "ff"),
+    ('\u{FB01}', "fi"),
+    ('\u{FB02}', "fl"),
+    ('\u{FB03}', "ffi"),
+    ('\u{FB04}', "ffl"),
+    ('\u{FB05}', "st"),
+    ('\u{FB06}', "st"),
+    ('\u{FB13}', "\u{574}\u{576}"),
+    ('\u{FB14}', "\u{574}\u{565}"),
+    ('\u{FB15}', "\u{574}\u{56B}"),
+    ('\u{FB16}', "\u{57E}\u{576}"),
+    ('\u{FB17}', "\u{574}\u{56D}"),
+];
+
+fn special_fold(c: char) -> Option<&'static str> {
+    if c.is_ascii() {
+        return None;
+    }
+    SPECIAL_FOLDS.binary_search_by_key(&c, |&(from, _)| from).ok().map(|i| SPECIAL_FOLDS[i].1)
+}
+
+/// Returns the full case folding of a single character, which may be more
+/// than one character long.
+fn fold_char(c: char) -> SmallVec<[char; 3]> {
+    let mut out = SmallVec::new();
+    if c.is_ascii() {
+        out.push(c.to_ascii_lowercase());
+        return out;
+    }
+    for lower in c.to_lowercase() {
+        match special_fold(lower) {
+            Some(folded) => out.extend(folded.chars()),
+            None => out.push(lower),
+        }
+    }
+    out
+}
+
+/// Returns the full Unicode case folding of `s` (the default folding, not
+/// the Turkic variant). This is the normalization used for case insensitive
+/// literal matching.
+pub fn fold_case(s: &str) -> String {
+    s.chars().flat_map(fold_char).collect()
 }
 
-/// Scan for a codepoint that, after conversion to lowercase, matches the probe.
-fn scan_lowercase(probe: char, s: &str) -> Option<usize> {
+/// Scan for a codepoint whose case folding starts with the probe.
+fn scan_folded(probe: char, s: &str) -> Option<usize> {
     for (i, c) in s.char_indices() {
-        if c.to_lowercase().next().unwrap() == probe {
+        if fold_char(c)[0] == probe {
             return Some(i);
         }
     }
@@ -337,6 +838,7 @@ fn scan_lowercase(probe: char, s: &str) -> Option<usize> {
 mod tests {
     use super::CaseMatching::{CaseInsensitive, Exact};
     use super::*;
+    use crate::helpers::string_leaf::MAX_LEAF;
     use crate::rope::Rope;
     use crate::tree::Cursor;
     use regex::RegexBuilder;
@@ -442,6 +944,93 @@ mod tests {
         assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "\u{212A}", None), Some(0));
     }
 
+    #[test]
+    fn find_casei_folding() {
+        let a = Rope::from("Die Straße, die STRASSE.");
+        let mut c = Cursor::new(&a, 0);
+        let mut raw_lines = a.lines_raw(..);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "STRASSE", None), Some(4));
+        assert_eq!(c.pos(), 11);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "STRASSE", None), Some(17));
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "STRASSE", None), None);
+        c.set(0);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "straße", None), Some(4));
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "straße", None), Some(17));
+        c.set(0);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "sse", None), Some(8));
+
+        let a = Rope::from("L'ÉCOLE, l'école; ΣΟΦΟΣ σοφος");
+        let mut c = Cursor::new(&a, 0);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "école", None), Some(2));
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "école", None), Some(12));
+        c.set(0);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "ÉCOLE", None), Some(2));
+        c.set(0);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "σοφος", None), Some(20));
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "σοφος", None), Some(31));
+        c.set(0);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "ΣΟΦΟΣ", None), Some(20));
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "ΣΟΦΟΣ", None), Some(31));
+
+        let a = Rope::from("a \u{FB01}le");
+        let mut c = Cursor::new(&a, 0);
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "FILE", None), Some(2));
+    }
+
+    #[test]
+    fn fold_case_examples() {
+        assert_eq!(fold_case("STRASSE"), fold_case("straße"));
+        assert_eq!(fold_case("\u{1E9E}"), "ss");
+        assert_eq!(fold_case("ΌΣΟΣ"), fold_case("όσος"));
+        assert_eq!(fold_case("İ"), "i\u{307}");
+        assert_eq!(fold_case("Ünïcödé"), "ünïcödé");
+        assert_eq!(fold_case("5\u{B5}s"), fold_case("5\u{39C}S"));
+        assert_eq!(fold_case("\u{1F88}"), "\u{1F00}\u{3B9}");
+        assert_eq!(fold_case("\u{FB13}"), "\u{574}\u{576}");
+    }
+
+    #[test]
+    fn fold_case_matches_case_mappings() {
+        // Full case folding is derived from the full case mappings: a
+        // character folds to the lowercase of the uppercase of its lowercase,
+        // except that Cherokee folds to uppercase and dotless i is kept.
+        let cherokee = |c: char| {
+            ('\u{13A0}'..='\u{13FD}').contains(&c) || ('\u{AB70}'..='\u{ABBF}').contains(&c)
+        };
+        for c in (0..=0x10FFFF).filter_map(std::char::from_u32) {
+            let expected: String = if c == '\u{131}' {
+                c.to_string()
+            } else if cherokee(c) {
+                c.to_uppercase().collect()
+            } else {
+                c.to_lowercase().flat_map(char::to_uppercase).flat_map(char::to_lowercase).collect()
+            };
+            assert_eq!(fold_char(c).iter().collect::<String>(), expected, "{:?}", c);
+        }
+        assert!(SPECIAL_FOLDS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(SPECIAL_FOLDS.iter().all(|&(c, _)| c.to_lowercase().eq(Some(c))));
+    }
+
+    #[test]
+    fn find_casei_partial_fold() {
+        let a = Rope::from("straße");
+        let mut c = Cursor::new(&a, 0);
+        let mut raw_lines = a.lines_raw(0..a.len());
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "stras", None), None);
+
+        let a = Rope::from("ß s");
+        let mut c = Cursor::new(&a, 0);
+        let mut raw_lines = a.lines_raw(0..a.len());
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "s", None), Some(3));
+        assert_eq!(c.pos(), 4);
+
+        let a = Rope::from("STRASSE straße");
+        let mut c = Cursor::new(&a, 1);
+        let mut raw_lines = a.lines_raw(1..a.len());
+        assert_eq!(find(&mut c, &mut raw_lines, CaseInsensitive, "strasse", None), Some(8));
+        assert_eq!(c.pos(), a.len());
+    }
+
     #[test]
     fn find_casei_0xc4() {
         let a = Rope::from("\u{0100}I");
@@ -753,4 +1342,201 @@ mod tests {
         c.set(2000);
         assert!(compare_cursor_str(&mut c, &mut raw_lines, &s[2000..]).is_some());
     }
+
+    #[test]
+    fn find_captures_groups() {
+        let a = Rope::from("let x = 1;\nlet foo = 42;\nconst y = 7;");
+        let regex = RegexBuilder::new(r"let (\w+) = (\d+)").build().unwrap();
+        let mut c = Cursor::new(&a, 0);
+        let mut raw_lines = a.lines_raw(..);
+
+        let groups = find_captures(&mut c, &mut raw_lines, &regex).unwrap();
+        assert_eq!(groups, vec![Some(0..9), Some(4..5), Some(8..9)]);
+        assert_eq!(c.pos(), 9);
+
+        raw_lines = a.lines_raw(c.pos()..);
+        let groups = find_captures(&mut c, &mut raw_lines, &regex).unwrap();
+        assert_eq!(groups, vec![Some(11..23), Some(15..18), Some(21..23)]);
+        assert_eq!(a.slice_to_cow(groups[1].clone().unwrap()), "foo");
+        assert_eq!(a.slice_to_cow(groups[2].clone().unwrap()), "42");
+
+        raw_lines = a.lines_raw(c.pos()..);
+        assert_eq!(find_captures(&mut c, &mut raw_lines, &regex), None);
+
+        let regex = RegexBuilder::new(r"(x)|(y)").build().unwrap();
+        let mut c = Cursor::new(&a, 12);
+        let mut raw_lines = a.lines_raw(12..);
+        let groups = find_captures(&mut c, &mut raw_lines, &regex).unwrap();
+        assert_eq!(groups, vec![Some(31..32), None, Some(31..32)]);
+    }
+
+    #[test]
+    fn find_multiline_literal() {
+        let mut s = "x".repeat(MAX_LEAF - 3);
+        s.push_str("one\ntwo\nthree");
+        let a = Rope::from(&s);
+        let expected = Some(MAX_LEAF - 3);
+        for &(cm, pat) in &[(Exact, "one\ntwo"), (CaseInsensitive, "ONE\nTwo")] {
+            let mut c = Cursor::new(&a, 0);
+            let mut raw_lines = a.lines_raw(..);
+            assert_eq!(find(&mut c, &mut raw_lines, cm, pat, None), expected);
+            assert_eq!(c.pos(), MAX_LEAF + 4);
+        }
+
+        // the same query escaped for regex mode spans the line break too
+        let regex = Regex::new(&regex::escape("one\ntwo")).unwrap();
+        let mut c = Cursor::new(&a, 0);
+        let mut raw_lines = a.lines_raw(..);
+        assert_eq!(find(&mut c, &mut raw_lines, Exact, "one\ntwo", Some(&regex)), expected);
+        assert_eq!(c.pos(), MAX_LEAF + 4);
+    }
+
+    #[test]
+    fn find_in_intervals_selections() {
+        let a = Rope::from("cat dog cat dog cat\ncatalog");
+        let selections = [Interval::new(0, 6), Interval::new(8, 14), Interval::new(16, 22)];
+        // the "cat" of "catalog" runs past the end of the last selection
+        let found = find_in_intervals(&a, &selections, "cat", Exact, None);
+        assert_eq!(found, vec![0..3, 8..11, 16..19]);
+
+        // "dog" at 4 straddles the end of the first selection
+        assert!(find_in_intervals(&a, &selections, "dog", Exact, None).is_empty());
+        assert_eq!(find_in_intervals(&a, &selections, "CAT", CaseInsensitive, None).len(), 3);
+
+        let regex = Regex::new("c.t").unwrap();
+        let found = find_in_intervals(&a, &selections, "c.t", Exact, Some(&regex));
+        assert_eq!(found, vec![0..3, 8..11, 16..19]);
+
+        // overlapping selections report each match once
+        let overlapping = [Interval::new(0, 12), Interval::new(6, 26)];
+        let found = find_in_intervals(&a, &overlapping, "cat", Exact, None);
+        assert_eq!(found, vec![0..3, 8..11, 16..19, 20..23]);
+    }
+
+    #[test]
+    fn refine_matches_reuses_previous() {
+        let text = "start stack stash test stall st\\nstatic ".repeat(40);
+        let rope = Rope::from(text.as_str());
+        let whole = [Interval::new(0, rope.len())];
+        let prev = find_in_intervals(&rope, &whole, "st", CaseMatching::Exact, None);
+        let fresh = find_in_intervals(&rope, &whole, "sta", CaseMatching::Exact, None);
+
+        assert_eq!(refine_matches(&rope, "st", &prev, "sta"), fresh);
+        let mut verified = 0;
+        let refined = refine_from_previous(&rope, "st", &prev, "sta", &mut verified).unwrap();
+        assert_eq!(refined, fresh);
+        assert_eq!(verified, prev.len());
+        assert!(verified < rope.len() / 4);
+
+        // not an extension, or a self-overlapping query: full search
+        assert!(refine_from_previous(&rope, "st", &prev, "ta", &mut verified).is_none());
+        let rope = Rope::from("aaab aab");
+        let whole = [Interval::new(0, rope.len())];
+        let prev = find_in_intervals(&rope, &whole, "aa", CaseMatching::Exact, None);
+        assert!(refine_from_previous(&rope, "aa", &prev, "aab", &mut verified).is_none());
+        assert_eq!(refine_matches(&rope, "aa", &prev, "aab"), vec![1..4, 5..8]);
+    }
+
+    #[test]
+    fn find_regex_multiline_spans_lines() {
+        let text = format!("{}\nSTART here\nmiddle line\nthe END\ntail END", "x".repeat(MAX_LEAF));
+        let rope = Rope::from(text.as_str());
+        let start = text.find("START").unwrap();
+        let end = text.find("the END").unwrap() + "the END".len();
+
+        let lazy = Regex::new("(?s)START.*?END").unwrap();
+        assert_eq!(find_regex_multiline(&rope, &lazy, 0), Some(start..end));
+        let greedy = Regex::new("(?s)START.*END").unwrap();
+        assert_eq!(find_regex_multiline(&rope, &greedy, 0), Some(start..text.len()));
+        let class = Regex::new(r"START[\s\S]*?END").unwrap();
+        assert_eq!(find_regex_multiline(&rope, &class, 0), Some(start..end));
+        assert_eq!(find_regex_multiline(&rope, &lazy, start + 1), None);
+
+        // anchors see the text before `start`
+        let anchored = Regex::new("^START").unwrap();
+        assert_eq!(find_regex_multiline(&rope, &anchored, start), None);
+        let line_anchored = Regex::new("(?m)^START").unwrap();
+        assert_eq!(find_regex_multiline(&rope, &line_anchored, 0), Some(start..start + 5));
+    }
+
+    #[test]
+    fn find_positions_cjk_columns() {
+        let rope = Rope::from("first line\n東京 and 大阪\nlast: 大阪\n");
+        let found = find_positions(&rope, "大阪", CaseMatching::Exact, None);
+        let second = "first line\n".len();
+        let third = rope.offset_of_line(2);
+        assert_eq!(
+            found,
+            vec![
+                (second + 11..second + 17, 1, 7),
+                (third + "last: ".len()..third + "last: 大阪".len(), 2, 6),
+            ]
+        );
+        assert_eq!(rope.position_of_offset(second + 3), (1, 1));
+        assert_eq!(rope.position_of_offset(rope.len()), (3, 0));
+        assert_eq!(rope.position_of_offset(0), (0, 0));
+        // inside a character, and a line spanning several leaves
+        assert_eq!(rope.position_of_offset(second + 1), (1, 0));
+        let long = Rope::from(format!("x\n{}", "東".repeat(1000)));
+        assert_eq!(long.position_of_offset(long.len()), (1, 1000));
+    }
+
+    #[test]
+    #[should_panic]
+    fn position_of_offset_past_end() {
+        Rope::from("ab\ncd").position_of_offset(6);
+    }
+
+    #[test]
+    fn count_match_lines_dedups() {
+        let rope = Rope::from("foo bar foo\nnothing\nFOO\nend foo");
+        assert_eq!(rope.count_match_lines("foo", CaseMatching::Exact, None), 2);
+        assert_eq!(rope.count_match_lines("foo", CaseMatching::CaseInsensitive, None), 3);
+        assert_eq!(rope.count_match_lines("missing", CaseMatching::Exact, None), 0);
+
+        let regex = Regex::new("o+").unwrap();
+        assert_eq!(rope.count_match_lines("o+", CaseMatching::Exact, Some(&regex)), 3);
+
+        let many = Rope::from("ab ab ab\n".repeat(300));
+        assert_eq!(many.count_match_lines("ab", CaseMatching::Exact, None), 300);
+    }
+
+    #[cfg(feature = "aho-corasick")]
+    #[test]
+    fn multi_find_overlapping_keywords() {
+        let finder = MultiFind::new(&["he", "she", "hers"], CaseMatching::Exact);
+        let rope = Rope::from("ushers");
+        let mut found = finder.find_all(&rope);
+        found.sort_by_key(|(p, r)| (r.start, *p));
+        assert_eq!(found, vec![(1, 1..4), (0, 2..4), (2, 2..6)]);
+
+        // matches spanning leaves, found in one pass
+        use crate::helpers::string_leaf::MIN_LEAF;
+        use crate::tree::TreeBuilder;
+
+        let filler = "x".repeat(MIN_LEAF - 2);
+        let mut b = TreeBuilder::new();
+        b.push_leaf_exact(filler.clone() + "sh");
+        b.push_leaf_exact("ers".to_owned() + &filler + "She");
+        b.push_leaf_exact("rs".to_owned() + &filler);
+        let rope: Rope = b.build();
+        assert_eq!(rope.iter_chunks(..).count(), 3);
+        let (first, second) = (MIN_LEAF - 2, 2 * MIN_LEAF + 1);
+        let mut expected = vec![
+            (1, first..first + 3),
+            (0, first + 1..first + 3),
+            (2, first + 1..first + 5),
+            (0, second + 1..second + 3),
+            (2, second + 1..second + 5),
+        ];
+        let mut found = finder.find_all(&rope);
+        found.sort_by_key(|(p, r)| (r.start, *p));
+        assert_eq!(found, expected);
+
+        let insensitive = MultiFind::new(&["he", "she", "hers"], CaseMatching::CaseInsensitive);
+        let mut found = insensitive.find_all(&rope);
+        found.sort_by_key(|(p, r)| (r.start, *p));
+        expected.insert(3, (1, second..second + 3));
+        assert_eq!(found, expected);
+    }
 }
@@ -0,0 +1,362 @@
+//! Grapheme cluster counts that can be summed across leaves.
+//!
+//! Counting clusters per leaf and adding the counts up is wrong whenever a
+//! cluster straddles a leaf boundary (a base character and its combining
+//! mark, a CR LF pair, an emoji ZWJ sequence, a regional indicator pair).
+//! A `GraphemeSummary` therefore keeps, next to the isolated count, the first
+//! and last few `char`s of the text it summarizes, and corrects the sum
+//! when two summaries are joined.
+//!
+//! `RopeInfo` carries one of these summaries, so the cluster count of a
+//! whole rope is read off its root.
+//!
+//! # Fusion rule
+//!
+//! The UAX #29 rules only ever look *backward*: whether there is a boundary
+//! before a character depends on that character and the text preceding it.
+//! Appending text on the right thus never moves a boundary in the left
+//! part, and the clusters of `left + right` are
+//!
+//! ```text
+//! count(left) + count(right) + (boundaries of right in context) - (boundaries of right alone)
+//! ```
+//!
+//! where the last two terms only differ near the start of `right`. They are
+//! computed by segmenting `tail(left) + head(right)` and `head(right)` and
+//! counting the boundaries that fall inside `head(right)`, including its
+//! first position (always a boundary when segmented alone, and the junction
+//! when segmented in context).
+//!
+//! The rules that look back further than one character follow runs of
+//! regional indicators, of extending characters before a ZWJ, or of
+//! extending characters and linkers in an Indic conjunct. Any other
+//! character ends such a run, so no boundary after it depends on the text
+//! before it, and only the boundaries of `head(right)` up to that character
+//! need comparing. The correction is therefore exact when the segmenter
+//! needs no context before `tail(left)`, and `head(right)` either is all of
+//! `right` or contains a character ending every run. Otherwise the summary
+//! is marked inexact, and the count is taken by segmenting the whole text
+//! instead. Only pathological text gets there: a run of more than eight
+//! marks or regional indicators crossing a leaf boundary. When both
+//! characters at the junction are ASCII, or common letters such as CJK
+//! ideographs, kana and Hangul syllables, no segmentation is needed at all: the junction is a
+//! boundary unless it splits a CR LF pair, and such a character ends every
+//! run.
+
+use std::ops::Range;
+
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+use crate::tree::NodeInfo;
+
+/// Number of `char`s remembered at each end of a summary.
+const WINDOW: usize = 8;
+
+/// Up to `WINDOW` chars from one end of a piece of text.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct CharWindow {
+    chars: [char; WINDOW],
+    len: u8,
+}
+
+impl CharWindow {
+    const EMPTY: CharWindow = CharWindow { chars: ['\0'; WINDOW], len: 0 };
+
+    fn from_chars<I: Iterator<Item = char>>(iter: I) -> CharWindow {
+        let mut result = CharWindow::EMPTY;
+        for c in iter.take(WINDOW) {
+            result.chars[result.len as usize] = c;
+            result.len += 1;
+        }
+        result
+    }
+
+    fn head(s: &str) -> CharWindow {
+        CharWindow::from_chars(s.chars())
+    }
+
+    fn tail(s: &str) -> CharWindow {
+        let mut result = CharWindow::from_chars(s.chars().rev());
+        result.chars[..result.len as usize].reverse();
+        result
+    }
+
+    fn as_slice(&self) -> &[char] {
+        &self.chars[..self.len as usize]
+    }
+
+    /// The first `WINDOW` chars of `self` followed by `other`.
+    fn join_head(&self, other: &CharWindow) -> CharWindow {
+        if self.len as usize == WINDOW {
+            return *self;
+        }
+        CharWindow::from_chars(self.as_slice().iter().chain(other.as_slice()).cloned())
+    }
+
+    /// The last `WINDOW` chars of `self` followed by `other`.
+    fn join_tail(&self, other: &CharWindow) -> CharWindow {
+        if other.len as usize == WINDOW {
+            return *other;
+        }
+        let joined = self.as_slice().iter().chain(other.as_slice());
+        let mut result = CharWindow::from_chars(joined.rev().cloned());
+        result.chars[..result.len as usize].reverse();
+        result
+    }
+
+    fn write_to(&self, buf: &mut [u8]) -> usize {
+        let mut len = 0;
+        for c in self.as_slice() {
+            len += c.encode_utf8(&mut buf[len..]).len();
+        }
+        len
+    }
+}
+
+/// A grapheme cluster count that can be accumulated across leaves.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GraphemeSummary {
+    count: usize,
+    chars: usize,
+    exact: bool,
+    head: CharWindow,
+    tail: CharWindow,
+}
+
+impl GraphemeSummary {
+    pub(crate) fn compute(s: &str) -> GraphemeSummary {
+        let (count, chars) = if s.is_ascii() {
+            // In ASCII text, only CR LF forms a cluster of more than one char.
+            (s.len() - s.matches("\r\n").count(), s.len())
+        } else if s.chars().all(is_plain) {
+            let chars = s.chars().count();
+            (chars - s.matches("\r\n").count(), chars)
+        } else {
+            (s.graphemes(true).count(), s.chars().count())
+        };
+        GraphemeSummary {
+            count,
+            chars,
+            exact: true,
+            head: CharWindow::head(s),
+            tail: CharWindow::tail(s),
+        }
+    }
+
+    /// The number of extended grapheme clusters in the summarized text, or
+    /// `None` if a junction could not be corrected exactly.
+    pub(crate) fn count(&self) -> Option<usize> {
+        if self.exact {
+            Some(self.count)
+        } else {
+            None
+        }
+    }
+}
+
+impl NodeInfo<String> for GraphemeSummary {
+    fn accumulate(&mut self, other: &Self) {
+        if other.chars == 0 {
+            return;
+        }
+        if self.chars == 0 {
+            *self = *other;
+            return;
+        }
+        match junction_correction(self, other) {
+            Some(correction) if self.exact && other.exact => {
+                self.count = ((self.count + other.count) as isize + correction) as usize;
+            }
+            _ => self.exact = false,
+        }
+        self.chars += other.chars;
+        self.head = self.head.join_head(&other.head);
+        self.tail = self.tail.join_tail(&other.tail);
+    }
+
+    fn compute_info(s: &String) -> Self {
+        GraphemeSummary::compute(s)
+    }
+
+    fn identity() -> Self {
+        GraphemeSummary {
+            count: 0,
+            chars: 0,
+            exact: true,
+            head: CharWindow::EMPTY,
+            tail: CharWindow::EMPTY,
+        }
+    }
+}
+
+/// The change in cluster count caused by placing `right` right after
+/// `left`, or `None` if the windows can't tell; see the module
+/// documentation.
+fn junction_correction(left: &GraphemeSummary, right: &GraphemeSummary) -> Option<isize> {
+    let (tail, head) = (&left.tail, &right.head);
+    let last = tail.chars[tail.len as usize - 1];
+    let first = head.chars[0];
+    if is_plain(last) && is_plain(first) {
+        return Some(if last == '\r' && first == '\n' { -1 } else { 0 });
+    }
+    // Only the boundaries up to the first char ending every run can move.
+    let checked = match head.as_slice().iter().position(|&c| ends_runs(c)) {
+        Some(i) => i + 1,
+        None if right.chars > WINDOW => return None,
+        None => head.len as usize,
+    };
+    let checked_len: usize = head.as_slice()[..checked].iter().map(|c| c.len_utf8()).sum();
+    let mut buf = [0u8; 2 * WINDOW * 4];
+    let junction = tail.write_to(&mut buf);
+    let len = junction + head.write_to(&mut buf[junction..]);
+    let joined = std::str::from_utf8(&buf[..len]).unwrap();
+    let checked_end = junction + checked_len;
+    let in_context = count_boundaries(joined, junction..checked_end, left.chars > WINDOW)?;
+    let alone = count_boundaries(&joined[junction..], 0..checked_len, false)?;
+    Some(in_context as isize - alone as isize)
+}
+
+/// Whether no boundary after `c` depends on the text before it: `c` is not
+/// a regional indicator, and doesn't extend the character before it.
+fn ends_runs(c: char) -> bool {
+    if is_plain(c) {
+        return true;
+    }
+    if ('\u{1F1E6}'..='\u{1F1FF}').contains(&c) {
+        return false;
+    }
+    let mut buf = [b'a'; 5];
+    let len = 1 + c.encode_utf8(&mut buf[1..]).len();
+    let s = std::str::from_utf8(&buf[..len]).unwrap();
+    GraphemeCursor::new(1, len, true).is_boundary(s, 0).unwrap_or(false)
+}
+
+/// Whether `c` is ASCII or one of the common letters and punctuation that
+/// always start a cluster of their own after any of these characters, and
+/// end every run. Text made only of such characters has one cluster per
+/// char, except for CR LF pairs.
+fn is_plain(c: char) -> bool {
+    matches!(
+        c,
+        '\0'..='\u{7F}'
+            | '\u{C0}'..='\u{24F}'
+            | '\u{391}'..='\u{3C9}'
+            | '\u{410}'..='\u{44F}'
+            | '\u{3001}'..='\u{3003}'
+            | '\u{300C}'..='\u{300F}'
+            | '\u{3041}'..='\u{3096}'
+            | '\u{30A1}'..='\u{30FA}'
+            | '\u{30FC}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7A3}'
+            | '\u{FF01}'..='\u{FF5E}'
+    )
+}
+
+/// Counts the grapheme boundaries in `s` at the char boundaries in
+/// `range`, where `s` may be preceded by more text if `more_before` is set.
+/// Returns `None` if that text would be needed.
+fn count_boundaries(s: &str, range: Range<usize>, more_before: bool) -> Option<usize> {
+    // Placing the chunk one byte into the text makes the segmenter ask for
+    // context instead of assuming the start of the text.
+    let chunk_start = more_before as usize;
+    let mut count = 0;
+    for (i, _) in s[range.clone()].char_indices() {
+        let offset = chunk_start + range.start + i;
+        let mut cursor = GraphemeCursor::new(offset, chunk_start + s.len(), true);
+        if cursor.is_boundary(s, chunk_start).ok()? {
+            count += 1;
+        }
+    }
+    Some(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summarize(pieces: &[&str]) -> Option<usize> {
+        let mut summary = GraphemeSummary::identity();
+        for piece in pieces {
+            summary.accumulate(&GraphemeSummary::compute(piece));
+        }
+        summary.count()
+    }
+
+    #[test]
+    fn plain_chars_always_break() {
+        let before = [
+            "a",
+            "\r",
+            "e\u{301}",
+            "\u{1F468}\u{200D}",
+            "\u{915}\u{94D}",
+            "\u{1F1EB}",
+            "\u{AC00}",
+            "\u{AC01}",
+            "\u{30FC}",
+        ];
+        let after = ["a", "\u{AC00}", "\u{AC01}", "\u{4E00}"];
+        for c in (0..=0xFFFF).filter_map(std::char::from_u32).filter(|&c| is_plain(c)) {
+            for prefix in &before {
+                let s = format!("{}{}", prefix, c);
+                if prefix.ends_with('\r') && c == '\n' {
+                    continue;
+                }
+                assert_eq!(s.graphemes(true).next_back(), Some(c.to_string().as_str()), "{:?}", s);
+            }
+            for suffix in &after {
+                let s = format!("{}{}", c, suffix);
+                assert_eq!(s.graphemes(true).next(), Some(c.to_string().as_str()), "{:?}", s);
+            }
+            let mut buf = [b'a'; 5];
+            let len = 1 + c.encode_utf8(&mut buf[1..]).len();
+            let s = std::str::from_utf8(&buf[..len]).unwrap();
+            assert!(GraphemeCursor::new(1, len, true).is_boundary(s, 0).unwrap(), "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn junction_corrections() {
+        let cases: &[&[&str]] = &[
+            &["abc", "def"],
+            &["a\r", "\nb"],
+            &["e", "\u{301}x"],
+            &["\u{1F468}\u{200D}", "\u{1F469}\u{200D}\u{1F467}"],
+            &["\u{1F1EB}", "\u{1F1F7}\u{1F1E9}\u{1F1EA}"],
+            &["\u{1F1EB}\u{1F1F7}\u{1F1E9}", "\u{1F1EA}\u{1F1EB}"],
+            &["\u{1100}", "\u{1161}\u{11A8}"],
+            &["", "e\u{301}", "", "\u{302}"],
+        ];
+        for pieces in cases {
+            let whole = pieces.concat();
+            assert_eq!(summarize(pieces), Some(whole.graphemes(true).count()), "{:?}", pieces);
+        }
+    }
+
+    #[test]
+    fn long_runs_across_junction() {
+        let ri = "\u{1F1EB}";
+        let marks = "\u{301}".repeat(20);
+        let cases: Vec<Vec<String>> = vec![
+            // the pairing of every indicator on the right depends on the left
+            vec![ri.repeat(9), ri.repeat(9)],
+            vec![ri.repeat(3), ri.repeat(20)],
+            vec![format!("x{}", ri.repeat(11)), format!("{}y", ri.repeat(5))],
+            // a ZWJ sequence whose pictograph is out of the window
+            vec![format!("\u{1F468}{}\u{200D}", marks), "\u{1F469}".into()],
+            vec![format!("e{}", marks), marks.clone()],
+            vec!["e".into(), marks.clone(), "\u{1F1EB}x".into()],
+        ];
+        for pieces in &cases {
+            let whole = pieces.concat();
+            let expected = whole.graphemes(true).count();
+            let pieces: Vec<&str> = pieces.iter().map(String::as_str).collect();
+            let counted = summarize(&pieces);
+            assert!(counted.is_none() || counted == Some(expected), "{:?}", pieces);
+        }
+        // Runs that end within the windows are still corrected exactly.
+        let right = format!("{}abcdefghij", ri);
+        assert_eq!(summarize(&[&format!("x{}", ri.repeat(3)), &right]), Some(1 + 2 + 10));
+    }
+}
@@ -1 +1,2 @@
+pub(crate) mod grapheme_count;
 pub(crate) mod string_leaf;
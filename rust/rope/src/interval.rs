@@ -40,6 +40,23 @@ impl Interval {
         Interval { start, end }
     }
 
+    /// Construct the `Interval` of `len` units beginning at `start`.
+    ///
+    /// The end saturates at `usize::MAX` rather than overflowing.
+    pub fn from_start_len(start: usize, len: usize) -> Interval {
+        Interval::new(start, start.saturating_add(len))
+    }
+
+    /// Construct the `Interval` covering the closed range [start..=end], for
+    /// callers that count the last position as part of the range.
+    ///
+    /// The result is the half-open interval [start..end + 1). The end
+    /// saturates at `usize::MAX` rather than overflowing, so a range ending
+    /// at `usize::MAX` loses its last position.
+    pub fn closed(start: usize, end: usize) -> Interval {
+        Interval::new(start, end.saturating_add(1))
+    }
+
     #[deprecated(since = "0.3.0", note = "all intervals are now closed_open, use Interval::new")]
     pub fn new_closed_open(start: usize, end: usize) -> Interval {
         Self::new(start, end)
@@ -68,6 +85,16 @@ impl Interval {
         self.end
     }
 
+    /// The last position inside the interval, that is, `end - 1`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is empty, as it then has no last position.
+    pub fn end_inclusive(&self) -> usize {
+        assert!(!self.is_empty(), "end_inclusive of empty interval {:?}", self);
+        self.end - 1
+    }
+
     pub fn start_end(&self) -> (usize, usize) {
         (self.start, self.end)
     }
@@ -101,6 +128,15 @@ impl Interval {
         Interval { start, end: max(start, end) }
     }
 
+    /// Clamps both endpoints into `[0, len]`, for sanitizing an untrusted
+    /// interval before using it on a document of length `len`. An inverted
+    /// interval becomes the empty interval at its (clamped) start.
+    pub fn clamp_to(self, len: usize) -> Interval {
+        let start = min(self.start, len);
+        let end = min(self.end, len);
+        Interval { start, end: max(start, end) }
+    }
+
     // smallest interval that encloses both inputs; if the inputs are
     // disjoint, then it fills in the hole.
     pub fn union(&self, other: Interval) -> Interval {
@@ -140,6 +176,18 @@ impl Interval {
     pub fn size(&self) -> usize {
         self.end - self.start
     }
+
+    /// Splits `self` into consecutive sub-intervals of `size`, the last of
+    /// which may be shorter. An empty interval yields no windows.
+    ///
+    /// Panics if `size` is zero.
+    pub fn windows(self, size: usize) -> impl Iterator<Item = Interval> {
+        assert!(size > 0, "window size must be non-zero");
+        let end = self.end;
+        (self.start..end)
+            .step_by(size)
+            .map(move |start| Interval::new(start, min(start.saturating_add(size), end)))
+    }
 }
 
 impl fmt::Display for Interval {
@@ -266,6 +314,31 @@ mod tests {
         assert!(Interval::new(1, 2).intersect(Interval::new(2, 43)).is_empty());
     }
 
+    #[test]
+    fn clamp_to() {
+        assert_eq!(Interval::new(2, 5).clamp_to(10), Interval::new(2, 5));
+        assert_eq!(Interval::new(4, 15).clamp_to(10), Interval::new(4, 10));
+        assert_eq!(Interval::new(12, 20).clamp_to(10), Interval::new(10, 10));
+        assert_eq!(Interval { start: 7, end: 3 }.clamp_to(10), Interval::new(7, 7));
+        assert_eq!(Interval { start: 14, end: 3 }.clamp_to(10), Interval::new(10, 10));
+    }
+
+    #[test]
+    fn closed() {
+        assert_eq!(Interval::closed(2, 5), Interval::new(2, 6));
+        assert_eq!(Interval::closed(3, 3), Interval::new(3, 4));
+        assert_eq!(Interval::closed(2, 5).end_inclusive(), 5);
+        assert!(Interval::closed(2, 5).contains(5));
+        assert!(!Interval::closed(2, 5).contains(6));
+        assert_eq!(Interval::closed(2, usize::MAX), Interval::new(2, usize::MAX));
+    }
+
+    #[test]
+    #[should_panic(expected = "empty interval")]
+    fn end_inclusive_empty() {
+        Interval::new(4, 4).end_inclusive();
+    }
+
     #[test]
     fn prefix() {
         assert_eq!(Interval::new(1, 2), Interval::new(1, 4).prefix(Interval::new(2, 3)));
@@ -282,4 +355,57 @@ mod tests {
         assert_eq!(0, Interval::new(1, 1).size());
         assert_eq!(1, Interval::new(1, 2).size());
     }
+
+    #[test]
+    fn windows() {
+        let windows = Interval::new(3, 13).windows(4).collect::<Vec<_>>();
+        assert_eq!(windows, vec![Interval::new(3, 7), Interval::new(7, 11), Interval::new(11, 13)]);
+        assert_eq!(Interval::new(0, 8).windows(4).count(), 2);
+        assert_eq!(Interval::new(5, 5).windows(3).count(), 0);
+
+        for size in 1..12 {
+            let iv = Interval::new(2, 25);
+            let mut next = iv.start();
+            for window in iv.windows(size) {
+                assert_eq!(window.start(), next);
+                assert!(!window.is_empty() && window.size() <= size);
+                next = window.end();
+            }
+            assert_eq!(next, iv.end());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn windows_zero_size() {
+        let _ = Interval::new(0, 4).windows(0);
+    }
+
+    #[test]
+    fn windows_huge_size() {
+        let windows = Interval::new(3, 13).windows(usize::MAX).collect::<Vec<_>>();
+        assert_eq!(windows, vec![Interval::new(3, 13)]);
+        let near_max = Interval::new(usize::MAX - 5, usize::MAX);
+        assert_eq!(near_max.windows(4).last(), Some(Interval::new(usize::MAX - 1, usize::MAX)));
+    }
+
+    #[test]
+    fn range_forms_into_interval() {
+        use crate::interval::IntervalBounds;
+
+        let len = 10;
+        assert_eq!((2..5).into_interval(len), Interval::new(2, 5));
+        assert_eq!((2..=5).into_interval(len), Interval::new(2, 6));
+        assert_eq!((..5).into_interval(len), Interval::new(0, 5));
+        assert_eq!((..=5).into_interval(len), Interval::new(0, 6));
+        assert_eq!((4..).into_interval(len), Interval::new(4, 10));
+        assert_eq!((10..).into_interval(len), Interval::new(10, 10));
+        assert_eq!((..).into_interval(len), Interval::new(0, 10));
+        assert_eq!((3..=usize::MAX).into_interval(len), Interval::new(3, usize::MAX));
+
+        assert_eq!(Interval::from_start_len(3, 4), Interval::new(3, 7));
+        assert_eq!(Interval::from_start_len(3, 0), Interval::new(3, 3));
+        assert_eq!(Interval::from_start_len(5, usize::MAX), Interval::new(5, usize::MAX));
+        assert_eq!(Interval::from_start_len(3, 4).into_interval(len), Interval::new(3, 7));
+    }
 }
@@ -20,6 +20,7 @@ use std::cmp;
 use crate::interval::Interval;
 use crate::tree::{Leaf, Node, NodeInfo, TreeBuilder};
 use std::fmt;
+use std::ops::Range;
 use std::slice;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -130,6 +131,29 @@ impl Subset {
         sb.build()
     }
 
+    /// Creates a `Subset` of a string of length `len` in which the elements in
+    /// `ranges` have a count of 1 and all others a count of 0.
+    ///
+    /// This is the set of deletions that removes `ranges` from the string.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ranges` are not sorted and non-overlapping, or if any
+    /// extends past `len`.
+    pub fn from_deleted_ranges(len: usize, ranges: &[Range<usize>]) -> Subset {
+        let mut sb = SubsetBuilder::new();
+        let mut prev_end = 0;
+        for range in ranges {
+            assert!(range.start <= range.end, "range {:?} is reversed", range);
+            assert!(prev_end <= range.start, "ranges must be sorted and non-overlapping");
+            assert!(range.end <= len, "range {:?} extends past length {}", range, len);
+            sb.add_range(range.start, range.end, 1);
+            prev_end = range.end;
+        }
+        sb.pad_to_len(len);
+        sb.build()
+    }
+
     /// Mostly for testing.
     pub fn delete_from_string(&self, s: &str) -> String {
         let mut result = String::new();
@@ -161,15 +185,30 @@ impl Subset {
     }
 
     /// Count the total length of all the segments matching `matcher`.
+    ///
+    /// Each element of the base sequence has a count, the number of times it
+    /// is in the subset; the engine uses it, for example, to track how many
+    /// times a character has been deleted. A count of zero means the element
+    /// is not in the subset (it is kept when deleting), and any other count
+    /// means it is.
     pub fn count(&self, matcher: CountMatcher) -> usize {
         self.segments.iter().filter(|seg| matcher.matches(seg)).map(|seg| seg.len).sum()
     }
 
-    /// Convenience alias for `self.count(CountMatcher::All)`
+    /// The length of the base sequence this subset applies to, whatever the
+    /// counts. Convenience alias for `self.count(CountMatcher::All)`.
     pub fn len(&self) -> usize {
         self.count(CountMatcher::All)
     }
 
+    /// The number of elements in the subset, that is with a non-zero count,
+    /// each counted once regardless of its count. These are the elements
+    /// removed by [`delete_from`](#method.delete_from). Convenience alias for
+    /// `self.count(CountMatcher::NonZero)`.
+    pub fn count_deleted(&self) -> usize {
+        self.count(CountMatcher::NonZero)
+    }
+
     /// Determine whether the subset is empty.
     /// In this case deleting it would do nothing.
     pub fn is_empty(&self) -> bool {
@@ -397,6 +436,10 @@ impl<'a> Iterator for SegmentTripleIter<'a> {
 }
 
 impl fmt::Debug for Subset {
+    /// By default, prints the ranges of elements that are in the subset
+    /// (`deleted`) and not in it (`kept`), as in
+    /// `Subset { len: 14, deleted: [6..14], kept: [0..6] }`.
+    ///
     /// Use the alternate flag (`#`) to print a more compact representation
     /// where each character represents the count of one element:
     /// '-' is 0, '#' is 1, 2-9 are digits, `+` is >9
@@ -418,7 +461,14 @@ impl fmt::Debug for Subset {
             }
             Ok(())
         } else {
-            f.debug_tuple("Subset").field(&self.segments).finish()
+            let ranges = |matcher| {
+                self.range_iter(matcher).map(|(start, end)| start..end).collect::<Vec<_>>()
+            };
+            f.debug_struct("Subset")
+                .field("len", &self.len())
+                .field("deleted", &ranges(CountMatcher::NonZero))
+                .field("kept", &ranges(CountMatcher::Zero))
+                .finish()
         }
     }
 }
@@ -748,6 +798,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn debug_ranges() {
+        let mut builder = SubsetBuilder::new();
+        builder.pad_to_len(2);
+        builder.add_range(2, 5, 3);
+        builder.add_range(6, 7, 1);
+        builder.pad_to_len(9);
+        let subset = builder.build();
+
+        assert_eq!(
+            format!("{:?}", subset),
+            "Subset { len: 9, deleted: [2..5, 6..7], kept: [0..2, 5..6, 7..9] }"
+        );
+        assert_eq!(format!("{:#?}", subset), "--333-#--");
+        assert_eq!(format!("{:?}", Subset::new(0)), "Subset { len: 0, deleted: [], kept: [] }");
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn subset_serialization_regression() {
@@ -763,4 +830,54 @@ mod tests {
         let json = serde_json::to_string(&subset).expect("subset should serialize");
         assert_eq!(json, SUBSET_FIXTURE.json);
     }
+
+    #[test]
+    fn len_and_count_deleted() {
+        let mut builder = SubsetBuilder::new();
+        builder.pad_to_len(2);
+        builder.add_range(2, 5, 3);
+        builder.add_range(6, 7, 1);
+        builder.pad_to_len(9);
+        let subset = builder.build();
+        assert_eq!(subset.len(), 9);
+        // the elements counted 3 times are each counted once
+        assert_eq!(subset.count_deleted(), 4);
+        assert_eq!(subset.len_after_delete(), 5);
+        assert_eq!(Subset::new(7).count_deleted(), 0);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn subset_fixture_totals() {
+        use crate::serde_fixtures::SUBSET_FIXTURE;
+
+        let subset: Subset = serde_json::from_str(SUBSET_FIXTURE.json).unwrap();
+        assert_eq!(subset.len(), 9);
+        assert_eq!(subset.count_deleted(), 4);
+        assert_eq!(subset.len_after_delete(), 5);
+    }
+
+    #[test]
+    fn from_deleted_ranges() {
+        let ranges = vec![0..2, 5..5, 5..9, 9..10, 14..16];
+        let subset = Subset::from_deleted_ranges(20, &ranges);
+        assert_eq!(subset.len(), 20);
+        let round_trip: Vec<_> =
+            subset.range_iter(CountMatcher::NonZero).map(|(start, end)| start..end).collect();
+        assert_eq!(round_trip, vec![0..2, 5..10, 14..16]);
+
+        let mut sb = SubsetBuilder::new();
+        for &(len, count) in &[(2, 1), (3, 0), (5, 1), (4, 0), (2, 1), (4, 0)] {
+            sb.push_segment(len, count);
+        }
+        assert_eq!(subset, sb.build());
+        assert_eq!(subset.delete_from_string("0123456789abcdefghij"), "234abcdghij");
+        assert_eq!(Subset::from_deleted_ranges(7, &[]), Subset::new(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "sorted and non-overlapping")]
+    fn from_deleted_ranges_overlapping() {
+        Subset::from_deleted_ranges(10, &[2..5, 4..6]);
+    }
 }
@@ -18,13 +18,20 @@
 #![allow(clippy::needless_return)]
 
 use std::borrow::Cow;
+use std::char::{decode_utf16, DecodeUtf16Error};
 use std::cmp::{min, Ordering};
+use std::collections::HashMap;
 use std::fmt;
-use std::ops::Add;
+use std::io;
+use std::iter::{FromIterator, Peekable};
+use std::ops::{Add, Range};
 use std::str::FromStr;
 use std::string::ParseError;
 
-use crate::delta::{Delta, DeltaElement};
+use crate::compare::ne_idx;
+use crate::delta::{Builder as DeltaBuilder, Delta, DeltaElement};
+use crate::find::{self, CaseMatching};
+use crate::helpers::grapheme_count::GraphemeSummary;
 use crate::helpers::string_leaf::{
     count_utf16_code_units, find_leaf_split_for_bulk, find_leaf_split_for_merge, MAX_LEAF, MIN_LEAF,
 };
@@ -36,6 +43,7 @@ use crate::metrics::{
 use crate::tree::{Cursor, DefaultMetricProvider, Leaf, Metric, Node, NodeInfo, TreeBuilder};
 
 use memchr::memchr;
+use regex::Regex;
 use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
 
 /// A rope data structure.
@@ -95,6 +103,26 @@ pub type RopeDelta = Delta<RopeInfo, String>;
 /// An element in a `RopeDelta`.
 pub type RopeDeltaElement = DeltaElement<RopeInfo, String>;
 
+/// The character sequence used to end lines of text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `"\n"`
+    Lf,
+    /// `"\r\n"`
+    CrLf,
+}
+
+/// A grapheme cluster laid out in a grid of character cells, as returned by
+/// [`Rope::grapheme_cells`].
+#[cfg(feature = "unicode-width")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphemeCell {
+    /// The byte range of the cluster in the rope.
+    pub byte_range: Range<usize>,
+    /// The number of columns the cluster occupies.
+    pub width: usize,
+}
+
 impl Leaf for String {
     fn len(&self) -> usize {
         self.len()
@@ -120,24 +148,42 @@ impl Leaf for String {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct RopeInfo {
     lines: usize,
     utf16_size: usize,
+    chars: usize,
+    ascii: bool,
+    graphemes: GraphemeSummary,
 }
 
 impl NodeInfo<String> for RopeInfo {
     fn accumulate(&mut self, other: &Self) {
         self.lines += other.lines;
         self.utf16_size += other.utf16_size;
+        self.chars += other.chars;
+        self.ascii &= other.ascii;
+        self.graphemes.accumulate(&other.graphemes);
     }
 
     fn compute_info(s: &String) -> Self {
-        RopeInfo { lines: count_newlines(s), utf16_size: count_utf16_code_units(s) }
+        RopeInfo {
+            lines: count_newlines(s),
+            utf16_size: count_utf16_code_units(s),
+            chars: bytecount::num_chars(s.as_bytes()),
+            ascii: s.is_ascii(),
+            graphemes: GraphemeSummary::compute(s),
+        }
     }
 
     fn identity() -> Self {
-        RopeInfo { lines: 0, utf16_size: 0 }
+        RopeInfo {
+            lines: 0,
+            utf16_size: 0,
+            chars: 0,
+            ascii: true,
+            graphemes: GraphemeSummary::identity(),
+        }
     }
 }
 
@@ -313,12 +359,69 @@ impl Metric<RopeInfo, String> for Utf16CodeUnitsMetric {
     }
 }
 
+/// Measures text in Unicode scalar values (Rust `char`s).
+#[derive(Clone, Copy)]
+pub struct CharsMetric(());
+
+impl Metric<RopeInfo, String> for CharsMetric {
+    fn measure(info: &RopeInfo, _: usize) -> usize {
+        info.chars
+    }
+
+    fn is_boundary(s: &String, offset: usize) -> bool {
+        is_codepoint_boundary(s.as_bytes(), offset)
+    }
+
+    fn to_base_units(s: &String, in_measured_units: usize) -> usize {
+        s.char_indices().nth(in_measured_units).map_or(s.len(), |(offset, _)| offset)
+    }
+
+    fn from_base_units(s: &String, in_base_units: usize) -> usize {
+        bytecount::num_chars(&s.as_bytes()[..in_base_units])
+    }
+
+    fn prev(s: &String, offset: usize) -> Option<usize> {
+        prev_codepoint_boundary(s.as_bytes(), offset)
+    }
+
+    fn next(s: &String, offset: usize) -> Option<usize> {
+        next_codepoint_boundary(s.as_bytes(), offset)
+    }
+
+    fn can_fragment() -> bool {
+        false
+    }
+}
+
 // Low level functions
 
 pub fn count_newlines(s: &str) -> usize {
     count_newlines_bytes(s.as_bytes())
 }
 
+/// Returns the polynomial hash of every `window`-byte window of `bytes`,
+/// indexed by the window's start offset.
+fn rolling_hashes(bytes: &[u8], window: usize) -> Vec<u64> {
+    const BASE: u64 = 0x100_0000_01b3;
+    if bytes.len() < window {
+        return Vec::new();
+    }
+    // BASE^(window - 1), the weight of the byte leaving the window.
+    let top = (1..window).fold(1u64, |acc, _| acc.wrapping_mul(BASE));
+    let mut hash =
+        bytes[..window].iter().fold(0u64, |h, &x| h.wrapping_mul(BASE).wrapping_add(x as u64));
+    let mut hashes = Vec::with_capacity(bytes.len() - window + 1);
+    hashes.push(hash);
+    for i in window..bytes.len() {
+        hash = hash
+            .wrapping_sub((bytes[i - window] as u64).wrapping_mul(top))
+            .wrapping_mul(BASE)
+            .wrapping_add(bytes[i] as u64);
+        hashes.push(hash);
+    }
+    hashes
+}
+
 // Additional APIs custom to strings
 
 impl FromStr for Rope {
@@ -339,16 +442,96 @@ impl Rope {
         self.edit(iv, new)
     }
 
+    /// Appends `s` to the end of the rope.
+    ///
+    /// A short string is merged into the last leaf when that leaf has room,
+    /// so repeated small appends keep the tree compact rather than adding a
+    /// leaf per call.
+    ///
+    /// Time complexity: O(log n + s.len())
+    pub fn push_str(&mut self, s: &str) {
+        if s.is_empty() {
+            return;
+        }
+        let suffix =
+            if s.len() <= MAX_LEAF { Rope::from_leaf(s.to_owned()) } else { Rope::from(s) };
+        *self = Rope::concat(std::mem::take(self), suffix);
+    }
+
+    /// Shortens the rope to `len` bytes, dropping the rest.
+    ///
+    /// Leaves entirely before `len` are shared with the original rope. Has no
+    /// effect if `len` is greater than or equal to the rope's length.
+    ///
+    /// Panics if `len` does not lie on a codepoint boundary.
+    ///
+    /// Time complexity: O(log n)
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return;
+        }
+        assert!(self.is_codepoint_boundary(len), "truncate offset is not a char boundary");
+        *self = self.subseq(..len);
+    }
+
+    /// Replaces each interval in `edits` with its rope, in a single pass over
+    /// the tree.
+    ///
+    /// The intervals refer to offsets in the rope before any of the edits,
+    /// and must be sorted and non-overlapping; adjacent edits are allowed.
+    /// This is equivalent to applying the edits one at a time from last to
+    /// first, but builds the result once, taking O(edits * log n) time rather
+    /// than a tree edit per change: the text between edits is pushed as whole
+    /// subtrees, and only the leaves an edit touches are rebuilt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the intervals are out of order, overlap, or extend past the
+    /// end of the rope.
+    pub fn apply_edits(&mut self, edits: &[(Interval, Rope)]) {
+        let mut prev_end = 0;
+        for (iv, _) in edits {
+            assert!(prev_end <= iv.start(), "edits must be sorted and non-overlapping");
+            prev_end = iv.end();
+        }
+        assert!(prev_end <= self.len(), "edit extends past the end of the rope");
+
+        let mut b = TreeBuilder::new();
+        let mut copied = 0;
+        for (iv, new) in edits {
+            b.push_slice(self, Interval::new(copied, iv.start()));
+            b.push(new.clone());
+            copied = iv.end();
+        }
+        b.push_slice(self, Interval::new(copied, self.len()));
+        *self = b.build();
+    }
+
     /// Returns a new Rope with the contents of the provided range.
     pub fn slice<T: IntervalBounds>(&self, iv: T) -> Rope {
         self.subseq(iv)
     }
 
+    /// Returns the contents of the provided range as a string.
+    ///
+    /// When the range lies within a single leaf the result borrows from the
+    /// rope (`Cow::Borrowed`) and nothing is allocated; otherwise the pieces
+    /// are copied into a new `String` (`Cow::Owned`). Use [`slice`] instead
+    /// when a `Rope` is wanted.
+    ///
+    /// This is the same as [`slice_to_cow`].
+    ///
+    /// [`slice`]: #method.slice
+    /// [`slice_to_cow`]: #method.slice_to_cow
+    pub fn slice_str<T: IntervalBounds>(&self, range: T) -> Cow<'_, str> {
+        self.slice_to_cow(range)
+    }
+
     // encourage callers to use Cursor instead?
 
     /// Determine whether `offset` lies on a codepoint boundary.
     pub fn is_codepoint_boundary(&self, offset: usize) -> bool {
-        let mut cursor = Cursor::new(self, offset);
+        let cursor = Cursor::new(self, offset);
         cursor.is_boundary::<BaseMetric>()
     }
 
@@ -394,6 +577,134 @@ impl Rope {
         cursor.next_grapheme()
     }
 
+    /// Returns the grapheme clusters in `range` with their display widths,
+    /// for renderers that lay text out in a grid of cells.
+    ///
+    /// The width of a cluster is the width of its first scalar, so combining
+    /// marks and other extending characters add nothing to it. Control
+    /// characters, including line endings, have width 0. `range` should begin
+    /// on a grapheme boundary.
+    #[cfg(feature = "unicode-width")]
+    pub fn grapheme_cells<T: IntervalBounds>(&self, range: T) -> Vec<GraphemeCell> {
+        use unicode_width::UnicodeWidthChar;
+
+        let Interval { start, end } = range.into_interval(self.len());
+        let mut cursor = Cursor::new(self, start);
+        let mut cells = Vec::new();
+        for (offset, cluster) in cursor.grapheme_iter() {
+            if offset >= end {
+                break;
+            }
+            let base = cluster.chars().next().unwrap();
+            cells.push(GraphemeCell {
+                byte_range: offset..min(offset + cluster.len(), end),
+                width: base.width().unwrap_or(0),
+            });
+        }
+        cells
+    }
+
+    /// Returns an iterator over the grapheme clusters in `range`, from last
+    /// to first, as `(start_offset, cluster)` pairs.
+    ///
+    /// Each boundary is found by segmenting backwards with context from the
+    /// rest of the rope, so a cluster is yielded whole even when it spans
+    /// leaves. A cluster straddling the start of `range` is cut off there.
+    /// The end of `range` should be a grapheme boundary.
+    pub fn graphemes_rev<T: IntervalBounds>(
+        &self,
+        range: T,
+    ) -> impl Iterator<Item = (usize, String)> + '_ {
+        let Interval { start, end } = range.into_interval(self.len());
+        let mut cursor = Cursor::new(self, end);
+        let mut pos = end;
+        std::iter::from_fn(move || {
+            if pos <= start {
+                return None;
+            }
+            cursor.set(pos);
+            let prev = cursor.prev_grapheme()?.max(start);
+            let cluster = self.slice_to_cow(prev..pos).into_owned();
+            pos = prev;
+            Some((prev, cluster))
+        })
+    }
+
+    /// Returns `offset` if it lies on a grapheme cluster boundary, otherwise
+    /// the start of the cluster containing it.
+    ///
+    /// Unlike codepoint snapping, this never splits a cluster made of several
+    /// codepoints, such as an emoji ZWJ sequence or a letter with combining
+    /// marks, so it is suitable for choosing wrap points.
+    pub fn floor_grapheme_boundary(&self, offset: usize) -> usize {
+        if offset == 0 || offset >= self.len() {
+            return offset.min(self.len());
+        }
+        match self.prev_grapheme_offset(offset) {
+            Some(prev) if self.next_grapheme_offset(prev) != Some(offset) => prev,
+            _ => offset,
+        }
+    }
+
+    /// Returns `offset` if it lies on a grapheme cluster boundary, otherwise
+    /// the end of the cluster containing it.
+    pub fn ceil_grapheme_boundary(&self, offset: usize) -> usize {
+        if offset == 0 || offset >= self.len() {
+            return offset.min(self.len());
+        }
+        match self.next_grapheme_offset(offset) {
+            Some(next) if self.prev_grapheme_offset(next) != Some(offset) => next,
+            _ => offset,
+        }
+    }
+
+    /// Returns the byte interval and text of the grapheme cluster containing
+    /// `offset`, or `None` if `offset` is at or past the end of the rope.
+    ///
+    /// An offset inside a cluster snaps back to its start, as by
+    /// [`floor_grapheme_boundary`](#method.floor_grapheme_boundary), so the
+    /// whole cluster is returned.
+    pub fn grapheme_at(&self, offset: usize) -> Option<(Interval, String)> {
+        if offset >= self.len() {
+            return None;
+        }
+        let start = self.floor_grapheme_boundary(offset);
+        let end = self.next_grapheme_offset(start).unwrap_or(self.len());
+        Some((Interval::new(start, end), self.slice_to_cow(start..end).into_owned()))
+    }
+
+    /// Returns the number of `\n` characters in the rope.
+    ///
+    /// This is not the same as the number of lines; see
+    /// [`display_line_count`](#method.display_line_count).
+    ///
+    /// Time complexity: O(1)
+    pub fn newline_count(&self) -> usize {
+        self.measure::<LinesMetric>()
+    }
+
+    /// Returns the number of lines of text in the rope, as a user would count
+    /// them.
+    ///
+    /// An empty rope has no lines. Otherwise this is one more than the number
+    /// of newlines, less one if the rope ends with a newline, since that
+    /// newline terminates the last line rather than starting a new one. So
+    /// `""` has 0 lines, `"a"` and `"a\n"` have 1, and `"a\nb"` has 2.
+    ///
+    /// Time complexity: O(log n)
+    pub fn display_line_count(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        self.newline_count() + 1 - usize::from(self.ends_with_newline())
+    }
+
+    /// Returns `true` if the last byte of the rope is `\n`. An empty rope does
+    /// not end with a newline.
+    pub fn ends_with_newline(&self) -> bool {
+        !self.is_empty() && self.byte_at(self.len() - 1) == b'\n'
+    }
+
     /// Return the line number corresponding to the byte index `offset`.
     ///
     /// The line number is 0-based, thus this is equivalent to the count of newlines
@@ -409,6 +720,90 @@ impl Rope {
         self.count::<LinesMetric>(offset)
     }
 
+    /// Returns the 0-based line and column of the byte index `offset`, with
+    /// the column counted in Unicode scalar values from the start of the line.
+    /// An offset inside a character counts as that character's start.
+    ///
+    /// Time complexity: O(log n)
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `offset > self.len()`.
+    pub fn position_of_offset(&self, offset: usize) -> (usize, usize) {
+        let offset = self.at_or_prev_codepoint_boundary(offset).unwrap_or(0);
+        let line = self.line_of_offset(offset);
+        let line_start = self.offset_of_line(line);
+        (line, self.measure_range::<CharsMetric>(Interval::new(line_start, offset)))
+    }
+
+    /// Returns the number of distinct lines on which at least one match of
+    /// `query` starts, as for a "found in N lines" summary.
+    ///
+    /// This scans the rope once without collecting match ranges; see
+    /// [`find::count_match_lines`] for details.
+    ///
+    /// [`find::count_match_lines`]: ../find/fn.count_match_lines.html
+    ///
+    /// Can panic if `query` is empty.
+    pub fn count_match_lines(
+        &self,
+        query: &str,
+        case: CaseMatching,
+        regex: Option<&Regex>,
+    ) -> usize {
+        find::count_match_lines(self, query, case, regex)
+    }
+
+    /// Returns the position of the byte index `offset` as used by the
+    /// Language Server Protocol: the 0-based line, and the column in UTF-16
+    /// code units from the start of that line.
+    ///
+    /// Time complexity: O(log n)
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `offset > self.len()`.
+    pub fn lsp_position_of(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_of_offset(offset);
+        let line_start = self.offset_of_line(line);
+        let column = self.count::<Utf16CodeUnitsMetric>(offset)
+            - self.count::<Utf16CodeUnitsMetric>(line_start);
+        (line, column)
+    }
+
+    /// Returns the byte offset of a Language Server Protocol position, the
+    /// inverse of [`lsp_position_of`].
+    ///
+    /// As the protocol specifies, a column past the end of the line refers to
+    /// the end of the line, before its line ending. A column inside a
+    /// surrogate pair resolves to the end of that character.
+    ///
+    /// Time complexity: O(log n)
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `line` is greater than one plus the number
+    /// of lines, like [`offset_of_line`].
+    ///
+    /// [`lsp_position_of`]: #method.lsp_position_of
+    /// [`offset_of_line`]: #method.offset_of_line
+    pub fn offset_of_lsp_position(&self, line: usize, utf16_col: usize) -> usize {
+        let line_start = self.offset_of_line(line);
+        let line_end = if line < self.measure::<LinesMetric>() {
+            let next = self.offset_of_line(line + 1) - 1;
+            if next > line_start && self.byte_at(next - 1) == b'\r' {
+                next - 1
+            } else {
+                next
+            }
+        } else {
+            self.len()
+        };
+        let start_units = self.count::<Utf16CodeUnitsMetric>(line_start);
+        let line_units = self.count::<Utf16CodeUnitsMetric>(line_end) - start_units;
+        self.count_base_units::<Utf16CodeUnitsMetric>(start_units + min(utf16_col, line_units))
+    }
+
     /// Return the byte offset corresponding to the line number `line`.
     /// If `line` is equal to one plus the current number of lines,
     /// this returns the offset of the end of the rope. Arguments higher
@@ -435,6 +830,23 @@ impl Rope {
         }
     }
 
+    /// Returns the number of bytes spanned by the lines in `lines`, including
+    /// their newlines. The last line of the rope counts up to the end of the
+    /// rope, whether or not it ends with a newline. An empty range is 0.
+    ///
+    /// Time complexity: O(log n)
+    ///
+    /// # Panics
+    ///
+    /// Panics if `lines.end > self.measure::<LinesMetric>() + 1`, as
+    /// [`offset_of_line`](#method.offset_of_line) does.
+    pub fn byte_len_of_lines(&self, lines: Range<usize>) -> usize {
+        if lines.start >= lines.end {
+            return 0;
+        }
+        self.offset_of_line(lines.end) - self.offset_of_line(lines.start)
+    }
+
     /// Converts a UTF-8 byte offset into a zero-based line count.
     ///
     /// This portability shim mirrors `count::<LinesMetric>` for consumers in
@@ -471,6 +883,192 @@ impl Rope {
         self.count_base_units::<Utf16CodeUnitsMetric>(units)
     }
 
+    /// Appends the UTF-16 encoding of the rope to `out`, a chunk at a time.
+    pub fn encode_utf16_into(&self, out: &mut Vec<u16>) {
+        out.reserve(self.measure::<Utf16CodeUnitsMetric>());
+        for chunk in self.iter_chunks(..) {
+            out.extend(chunk.encode_utf16());
+        }
+    }
+
+    /// Decodes a rope from UTF-16 code units.
+    ///
+    /// Returns an error if `units` contains an unpaired surrogate.
+    pub fn from_utf16(units: &[u16]) -> Result<Rope, DecodeUtf16Error> {
+        let mut b = TreeBuilder::new();
+        let mut leaf = String::with_capacity(MAX_LEAF);
+        for c in decode_utf16(units.iter().cloned()) {
+            let c = c?;
+            if leaf.len() + c.len_utf8() > MAX_LEAF {
+                b.push_leaf(std::mem::replace(&mut leaf, String::with_capacity(MAX_LEAF)));
+            }
+            leaf.push(c);
+        }
+        if !leaf.is_empty() {
+            b.push_leaf(leaf);
+        }
+        Ok(b.build())
+    }
+
+    /// Returns `true` if the rope contains only ASCII text.
+    ///
+    /// This is tracked in the tree, so takes constant time.
+    pub fn is_ascii(&self) -> bool {
+        self.info().ascii
+    }
+
+    /// Returns a 64-bit fingerprint of the rope's contents, suitable as a
+    /// cache key for data derived from the text.
+    ///
+    /// This is the FNV-1a hash of the UTF-8 bytes, so it depends only on the
+    /// contents and not on how they are split into leaves, and is the same
+    /// across processes. It is not cryptographic.
+    ///
+    /// Time complexity: O(n)
+    pub fn content_hash(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x100_0000_01b3;
+        self.iter_chunks(..)
+            .flat_map(str::bytes)
+            .fold(OFFSET_BASIS, |hash, b| (hash ^ b as u64).wrapping_mul(PRIME))
+    }
+
+    /// Returns `true` if the bytes of the rope in `iv` are exactly `bytes`.
+    ///
+    /// The lengths are compared first, then the range is compared one chunk
+    /// at a time, so nothing is allocated even when `iv` spans leaves. An
+    /// interval extending past the end of the rope is never equal.
+    pub fn range_eq_bytes(&self, iv: Interval, bytes: &[u8]) -> bool {
+        if iv.size() != bytes.len() || iv.end() > self.len() {
+            return false;
+        }
+        let mut rest = bytes;
+        for chunk in self.iter_chunks(iv) {
+            let (head, tail) = rest.split_at(chunk.len());
+            if chunk.as_bytes() != head {
+                return false;
+            }
+            rest = tail;
+        }
+        true
+    }
+
+    /// Returns the number of extended grapheme clusters in the rope.
+    ///
+    /// Cluster counts are cached in the tree, with a correction applied
+    /// where a cluster spans two leaves, so this takes constant time. Text
+    /// with a run of marks or regional indicators too long for that
+    /// correction to see across a leaf boundary is counted by iterating its
+    /// clusters instead.
+    pub fn grapheme_count(&self) -> usize {
+        match self.info().graphemes.count() {
+            Some(count) => count,
+            None => Cursor::new(self, 0).grapheme_iter().count(),
+        }
+    }
+
+    /// Converts a UTF-8 byte offset into the number of `char`s before it.
+    ///
+    /// An offset inside a multi-byte character is snapped down to the start
+    /// of that character, so it maps to the character's own index.
+    ///
+    /// Time complexity: O(log n)
+    pub fn byte_to_char(&self, byte: usize) -> usize {
+        let byte = self.at_or_prev_codepoint_boundary(byte).unwrap_or(0);
+        self.count::<CharsMetric>(byte)
+    }
+
+    /// Converts an index in `char`s into a UTF-8 byte offset. An index past
+    /// the last character maps to the end of the rope.
+    ///
+    /// Time complexity: O(log n)
+    pub fn char_to_byte(&self, ch: usize) -> usize {
+        if ch >= self.measure::<CharsMetric>() {
+            return self.len();
+        }
+        self.count_base_units::<CharsMetric>(ch)
+    }
+
+    /// Finds a long substring common to `a` and `b`, returning its byte
+    /// range in each rope.
+    ///
+    /// This is [`longest_common_span_with_window`] with a window of 32 bytes,
+    /// so common runs shorter than that are not found.
+    ///
+    /// [`longest_common_span_with_window`]: #method.longest_common_span_with_window
+    pub fn longest_common_span(a: &Rope, b: &Rope) -> Option<(Range<usize>, Range<usize>)> {
+        Rope::longest_common_span_with_window(a, b, 32)
+    }
+
+    /// Finds a long substring of at least `min_window` bytes common to `a`
+    /// and `b`, returning its byte range in each rope.
+    ///
+    /// Every `min_window`-byte window of `b` is indexed by a rolling hash, and
+    /// each window of `a` that matches one is extended as far as possible in
+    /// both directions. The longest such run is returned, trimmed to
+    /// codepoint boundaries.
+    ///
+    /// This is a heuristic, intended as an anchor for diffing: only the first
+    /// occurrence of each window in `b` is considered, and scanning of `a`
+    /// resumes after the end of each match, so the result is not guaranteed
+    /// to be maximal for large or repetitive inputs. Returns `None` if no
+    /// common run of `min_window` bytes is found.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_window` is zero.
+    pub fn longest_common_span_with_window(
+        a: &Rope,
+        b: &Rope,
+        min_window: usize,
+    ) -> Option<(Range<usize>, Range<usize>)> {
+        assert!(min_window > 0, "window size must be positive");
+        let a_text = a.slice_to_cow(..);
+        let b_text = b.slice_to_cow(..);
+        let (a_bytes, b_bytes) = (a_text.as_bytes(), b_text.as_bytes());
+
+        let mut b_windows = HashMap::new();
+        for (i, hash) in rolling_hashes(b_bytes, min_window).into_iter().enumerate() {
+            b_windows.entry(hash).or_insert(i);
+        }
+
+        let a_hashes = rolling_hashes(a_bytes, min_window);
+        let mut best: Option<(Range<usize>, Range<usize>)> = None;
+        let mut i = 0;
+        while i < a_hashes.len() {
+            let j = match b_windows.get(&a_hashes[i]) {
+                Some(&j) if a_bytes[i..i + min_window] == b_bytes[j..j + min_window] => j,
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let back = a_bytes[..i]
+                .iter()
+                .rev()
+                .zip(b_bytes[..j].iter().rev())
+                .take_while(|(x, y)| x == y)
+                .count();
+            let fwd = a_bytes[i..].iter().zip(&b_bytes[j..]).take_while(|(x, y)| x == y).count();
+            let (mut start, mut end) = (i - back, i + fwd);
+            // Both sides hold the same bytes, so trimming `a` to codepoint
+            // boundaries trims `b` to boundaries too.
+            while !a_text.is_char_boundary(start) {
+                start += 1;
+            }
+            while !a_text.is_char_boundary(end) {
+                end -= 1;
+            }
+            let len = end.saturating_sub(start);
+            if len >= min_window && best.as_ref().map_or(true, |(r, _)| len > r.len()) {
+                let b_start = start + j - i;
+                best = Some((start..end, b_start..b_start + len));
+            }
+            i += fwd.max(1);
+        }
+        best
+    }
+
     /// Returns an iterator over chunks of the rope.
     ///
     /// Each chunk is a `&str` slice borrowed from the rope's storage. The size
@@ -488,6 +1086,41 @@ impl Rope {
         ChunkIter { cursor: Cursor::new(self, start), end }
     }
 
+    /// Returns an iterator over chunks of the rope of at most `max` bytes.
+    ///
+    /// Chunks are borrowed from the rope's leaves, as with
+    /// [`iter_chunks`](#method.iter_chunks), but leaves longer than `max` are
+    /// split on `char` boundaries. A scalar value is never split, so if `max`
+    /// is less than 4, a character wider than `max` is yielded on its own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn iter_chunks_max<T: IntervalBounds>(
+        &self,
+        range: T,
+        max: usize,
+    ) -> impl Iterator<Item = Cow<'_, str>> {
+        assert!(max > 0, "chunk size must be positive");
+        self.iter_chunks(range).flat_map(move |mut chunk| {
+            std::iter::from_fn(move || {
+                if chunk.is_empty() {
+                    return None;
+                }
+                let mut end = max.min(chunk.len());
+                while !chunk.is_char_boundary(end) {
+                    end -= 1;
+                }
+                if end == 0 {
+                    end = chunk.chars().next().map_or(chunk.len(), char::len_utf8);
+                }
+                let (head, rest) = chunk.split_at(end);
+                chunk = rest;
+                Some(Cow::Borrowed(head))
+            })
+        })
+    }
+
     /// An iterator over the raw lines. The lines, except the last, include the
     /// terminating newline.
     ///
@@ -497,6 +1130,27 @@ impl Rope {
         LinesRaw { inner: self.iter_chunks(range), fragment: "" }
     }
 
+    /// An iterator over the raw lines in reverse order, starting from the
+    /// end of `range`. As with [`lines_raw`](#method.lines_raw), each line
+    /// includes its terminating newline; only the first line yielded, the
+    /// last line of the range, may lack one.
+    pub fn lines_raw_rev<T: IntervalBounds>(&self, range: T) -> LinesRawRev<'_> {
+        let Interval { start, end } = range.into_interval(self.len());
+        LinesRawRev { cursor: Cursor::new(self, end), start, end }
+    }
+
+    /// An iterator over the paragraphs in `range`, yielding the byte range and
+    /// text of each.
+    ///
+    /// A paragraph is a maximal run of non-blank lines, where a blank line is
+    /// one containing only whitespace. Its range covers its lines as produced
+    /// by [`lines_raw`](#method.lines_raw), so it includes the newline ending
+    /// its last line, if any.
+    pub fn paragraphs<T: IntervalBounds>(&self, range: T) -> Paragraphs<'_> {
+        let Interval { start, end } = range.into_interval(self.len());
+        Paragraphs { rope: self, lines: self.lines_raw(start..end).peekable(), offset: start }
+    }
+
     /// An iterator over the lines of a rope.
     ///
     /// Lines are ended with either Unix (`\n`) or MS-DOS (`\r\n`) style line endings.
@@ -511,6 +1165,305 @@ impl Rope {
         Lines { inner: self.lines_raw(range) }
     }
 
+    /// An iterator over the lines in `range`, yielding the index and byte
+    /// interval of each without touching the text in between.
+    ///
+    /// Lines are split as by [`lines_raw`](#method.lines_raw), so the first
+    /// and last intervals are cut off by `range`. If `include_ending` is
+    /// false, a trailing `\n` or `\r\n` is left out of each interval.
+    pub fn line_intervals<T: IntervalBounds>(
+        &self,
+        range: T,
+        include_ending: bool,
+    ) -> LineIntervals<'_> {
+        let Interval { start, end } = range.into_interval(self.len());
+        LineIntervals {
+            cursor: Cursor::new(self, start),
+            line: self.line_of_offset(start),
+            offset: start,
+            end,
+            include_ending,
+        }
+    }
+
+    /// Returns up to the first `k` lines of the rope, as produced by
+    /// [`lines`](#method.lines), and whether any lines were left out.
+    ///
+    /// Time complexity: proportional to the length of the returned lines;
+    /// iteration stops as soon as line `k + 1` is seen.
+    pub fn first_lines(&self, k: usize) -> (Vec<Cow<'_, str>>, bool) {
+        let mut lines = self.lines(..);
+        let first = lines.by_ref().take(k).collect();
+        (first, lines.next().is_some())
+    }
+
+    /// Returns the indentation of line `line`: the run of spaces and tabs at
+    /// its start, along with its length in bytes. For a blank line this is
+    /// all of the line's content before the newline.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as [`offset_of_line`](#method.offset_of_line).
+    pub fn indentation_of_line(&self, line: usize) -> (usize, Cow<'_, str>) {
+        let start = self.offset_of_line(line);
+        let mut len = 0;
+        for chunk in self.iter_chunks(start..) {
+            let indent = chunk.bytes().take_while(|&b| b == b' ' || b == b'\t').count();
+            len += indent;
+            if indent < chunk.len() {
+                break;
+            }
+        }
+        (len, self.slice_to_cow(start..start + len))
+    }
+
+    /// Returns the line ending used by most of the first 64 lines of the rope,
+    /// or `LineEnding::Lf` if it has no newlines.
+    ///
+    /// Only the start of the document is sampled, which is enough for files
+    /// with a consistent style without scanning the whole rope. Ties go to
+    /// `Lf`.
+    pub fn detect_line_ending(&self) -> LineEnding {
+        const SAMPLE_LINES: usize = 64;
+        let (mut lf, mut crlf) = (0, 0);
+        for line in self.lines_raw(..).take(SAMPLE_LINES) {
+            if line.ends_with("\r\n") {
+                crlf += 1;
+            } else if line.ends_with('\n') {
+                lf += 1;
+            }
+        }
+        if crlf > lf {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    /// Returns the range of the spaces and tabs at the end of each line, just
+    /// before its line ending (or the end of the rope). Lines without
+    /// trailing whitespace are skipped.
+    ///
+    /// Only the tail of each line is examined, walking back from its end.
+    pub fn trailing_whitespace_spans(&self) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut lines = Cursor::new(self, 0);
+        let mut line_start = 0;
+        while line_start < self.len() {
+            let line_end = lines.next::<LinesMetric>().unwrap_or(self.len());
+            let mut back = Cursor::new(self, line_end);
+            let mut end = line_end;
+            let mut start = line_end;
+            while start > line_start {
+                match back.prev_codepoint() {
+                    Some('\n') if start == line_end => end -= 1,
+                    Some('\r') if start == end && end + 1 == line_end => end -= 1,
+                    Some(' ') | Some('\t') => {}
+                    _ => break,
+                }
+                start -= 1;
+            }
+            if start < end {
+                spans.push(start..end);
+            }
+            line_start = line_end;
+        }
+        spans
+    }
+
+    /// Returns the number of occurrences of `ch` in the rope.
+    ///
+    /// ASCII characters are counted a chunk at a time with `bytecount`;
+    /// other characters fall back to scanning the chars of each chunk.
+    pub fn count_char(&self, ch: char) -> usize {
+        if ch.is_ascii() {
+            let byte = ch as u8;
+            self.iter_chunks(..).map(|chunk| bytecount::count(chunk.as_bytes(), byte)).sum()
+        } else {
+            self.iter_chunks(..).map(|chunk| chunk.chars().filter(|&c| c == ch).count()).sum()
+        }
+    }
+
+    /// Returns a delta that replaces every occurrence of `from` with `to`.
+    ///
+    /// Only the occurrences are touched; the text between them is copied, so
+    /// the delta stays small when matches are rare.
+    pub fn replace_char(&self, from: char, to: char) -> RopeDelta {
+        let mut builder = DeltaBuilder::new(self.len());
+        if from != to {
+            let to = Rope::from(to.to_string());
+            let mut offset = 0;
+            for chunk in self.iter_chunks(..) {
+                for (ix, _) in chunk.match_indices(from) {
+                    let start = offset + ix;
+                    builder.replace(start..start + from.len_utf8(), to.clone());
+                }
+                offset += chunk.len();
+            }
+        }
+        builder.build()
+    }
+
+    /// Returns a delta that deletes everything outside `iv`, leaving only the
+    /// text it covers.
+    ///
+    /// `iv` is clamped to the rope first.
+    pub fn keep_only(&self, iv: Interval) -> RopeDelta {
+        let iv = iv.clamp_to(self.len());
+        let mut builder = DeltaBuilder::new(self.len());
+        if iv.start > 0 {
+            builder.delete(0..iv.start);
+        }
+        if iv.end < self.len() {
+            builder.delete(iv.end..self.len());
+        }
+        builder.build()
+    }
+
+    /// Returns a delta appending `\n` if the rope is non-empty and does not
+    /// already end with a newline, or `None` if nothing needs to change.
+    pub fn ensure_final_newline(&self) -> Option<RopeDelta> {
+        if self.is_empty() || self.ends_with_newline() {
+            return None;
+        }
+        let mut builder = DeltaBuilder::new(self.len());
+        builder.replace(self.len()..self.len(), Rope::from("\n"));
+        Some(builder.build())
+    }
+
+    /// Returns a delta that replaces `iv` with `new_text`, with `base_indent`
+    /// prepended to each line of `new_text` after the first.
+    ///
+    /// The first line continues whatever precedes `iv` on its line, so it is
+    /// left as is. Blank lines are not indented, so no trailing whitespace is
+    /// introduced, and neither is the empty remainder after a final newline,
+    /// since the text following `iv` keeps its own indentation.
+    pub fn replace_reindented(&self, iv: Interval, new_text: &str, base_indent: &str) -> RopeDelta {
+        let mut text = String::with_capacity(new_text.len());
+        for (i, line) in new_text.split('\n').enumerate() {
+            if i > 0 {
+                text.push('\n');
+                if !line.trim_end_matches('\r').is_empty() {
+                    text.push_str(base_indent);
+                }
+            }
+            text.push_str(line);
+        }
+        let mut builder = DeltaBuilder::new(self.len());
+        builder.replace(iv, Rope::from(text));
+        builder.build()
+    }
+
+    /// Returns a delta applying `f` to each line in `lines`, a range of
+    /// 0-based line numbers.
+    ///
+    /// `f` is given the line number and the line's contents without its line
+    /// ending, and returns the new contents, or `None` to leave the line as it
+    /// is. Line endings are always kept, and unchanged lines are copied, so
+    /// the delta only touches lines that actually change. The range is
+    /// clamped to the lines of the rope; the empty remainder after a final
+    /// newline is not a line.
+    pub fn transform_lines<F>(&self, lines: Range<usize>, f: F) -> RopeDelta
+    where
+        F: Fn(usize, &str) -> Option<String>,
+    {
+        let mut builder = DeltaBuilder::new(self.len());
+        let last = self.measure::<LinesMetric>() + 1;
+        let (first, end) = (min(lines.start, last), min(lines.end, last));
+        if first < end {
+            let mut offset = self.offset_of_line(first);
+            let range = offset..self.offset_of_line(end);
+            for (line_num, line) in (first..).zip(self.lines_raw(range)) {
+                let content = line.trim_end_matches('\n').trim_end_matches('\r');
+                match f(line_num, content) {
+                    Some(new) if new != content => {
+                        builder.replace(offset..offset + content.len(), Rope::from(new));
+                    }
+                    _ => (),
+                }
+                offset += line.len();
+            }
+        }
+        builder.build()
+    }
+
+    /// Returns a delta inserting `prefix(n)` at the start of each line `n`
+    /// in `lines`, a range of 0-based line numbers.
+    ///
+    /// The range is clamped to the lines of the rope. A last line without a
+    /// line ending is prefixed like any other, while the empty remainder
+    /// after a final newline is not a line and gets no prefix.
+    pub fn prefix_lines<F>(&self, lines: Range<usize>, prefix: F) -> RopeDelta
+    where
+        F: Fn(usize) -> String,
+    {
+        let mut builder = DeltaBuilder::new(self.len());
+        let end = min(lines.end, self.display_line_count());
+        for line in lines.start..end {
+            let text = prefix(line);
+            if !text.is_empty() {
+                let offset = self.offset_of_line(line);
+                builder.replace(offset..offset, Rope::from(text));
+            }
+        }
+        builder.build()
+    }
+
+    /// Returns a delta deleting every line that is equal to the line before
+    /// it, as the `uniq` command does.
+    ///
+    /// Lines are compared without their line endings, and a deleted line
+    /// takes its line ending with it. A duplicate last line with no line
+    /// ending instead takes the ending of the line it duplicates, so the
+    /// result does not gain a trailing newline. Only adjacent duplicates are
+    /// removed.
+    pub fn dedup_adjacent_lines(&self) -> RopeDelta {
+        let mut deletions: Vec<Interval> = Vec::new();
+        let mut prev: Option<Cow<'_, str>> = None;
+        // the end of the contents of the last line kept
+        let mut kept_end = 0;
+        let mut offset = 0;
+        for line in self.lines_raw(..) {
+            let len = line.trim_end_matches('\n').trim_end_matches('\r').len();
+            let content = match &line {
+                Cow::Borrowed(s) => Cow::Borrowed(&s[..len]),
+                Cow::Owned(s) => Cow::Owned(s[..len].to_owned()),
+            };
+            let end = offset + line.len();
+            if prev.as_ref() == Some(&content) {
+                if len == line.len() {
+                    // the last line, and unterminated
+                    while deletions.last().is_some_and(|iv| iv.start() >= kept_end) {
+                        deletions.pop();
+                    }
+                    deletions.push(Interval::new(kept_end, end));
+                } else {
+                    deletions.push(Interval::new(offset, end));
+                }
+            } else {
+                kept_end = offset + len;
+                prev = Some(content);
+            }
+            offset = end;
+        }
+        let mut builder = DeltaBuilder::new(self.len());
+        for iv in deletions {
+            builder.delete(iv);
+        }
+        builder.build()
+    }
+
+    /// Returns the rope with a leading UTF-8 byte order mark removed, and
+    /// whether one was present.
+    pub fn strip_bom(&self) -> (Rope, bool) {
+        const BOM: &[u8] = b"\xEF\xBB\xBF";
+        if self.len() >= BOM.len() && BOM.iter().enumerate().all(|(i, &b)| self.byte_at(i) == b) {
+            (self.subseq(BOM.len()..), true)
+        } else {
+            (self.clone(), false)
+        }
+    }
+
     // callers should be encouraged to use cursor instead
     pub fn byte_at(&self, offset: usize) -> u8 {
         let cursor = Cursor::new(self, offset);
@@ -518,6 +1471,8 @@ impl Rope {
         leaf.as_bytes()[pos]
     }
 
+    /// Returns the contents of the provided range, borrowing from the rope
+    /// when the range lies within a single leaf and allocating otherwise.
     pub fn slice_to_cow<T: IntervalBounds>(&self, range: T) -> Cow<'_, str> {
         let mut iter = self.iter_chunks(range);
         let first = iter.next();
@@ -559,6 +1514,23 @@ impl<'a> Iterator for ChunkIter<'a> {
 }
 
 impl TreeBuilder<RopeInfo, String> {
+    /// Push a string as a single leaf, without merging it into the previous
+    /// leaf or splitting it.
+    ///
+    /// This is intended for tests and fixtures that need a tree with exactly
+    /// the given leaves. Unlike `push_leaf`, the leaf is never combined with
+    /// its neighbour, even when the previous leaf is short. Every leaf of a
+    /// tree with more than one leaf must still be at least `MIN_LEAF` bytes
+    /// long; debug builds check this when the tree is assembled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `s` is longer than `MAX_LEAF`.
+    pub fn push_leaf_exact(&mut self, s: String) {
+        assert!(s.len() <= MAX_LEAF, "exact leaf length {} exceeds {}", s.len(), MAX_LEAF);
+        self.push_leaf_unmerged(s);
+    }
+
     /// Push a string on the accumulating tree in the naive way.
     ///
     /// Splits the provided string in chunks that fit in a leaf
@@ -585,6 +1557,208 @@ impl<T: AsRef<str>> From<T> for Rope {
     }
 }
 
+/// Accumulates small pieces of text into leaf-sized chunks before handing
+/// them to a `TreeBuilder`, so that extending a rope is linear in the amount
+/// of text added.
+struct LeafAccumulator {
+    b: TreeBuilder<RopeInfo, String>,
+    buf: String,
+}
+
+impl LeafAccumulator {
+    fn new(initial: Rope) -> LeafAccumulator {
+        let mut b = TreeBuilder::new();
+        b.push(initial);
+        LeafAccumulator { b, buf: String::with_capacity(MAX_LEAF) }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        if self.buf.len() + s.len() > MAX_LEAF {
+            self.flush();
+            if s.len() > MAX_LEAF {
+                self.b.push_str(s);
+                return;
+            }
+        }
+        self.buf.push_str(s);
+    }
+
+    fn flush(&mut self) {
+        if !self.buf.is_empty() {
+            let leaf = std::mem::replace(&mut self.buf, String::with_capacity(MAX_LEAF));
+            self.b.push_leaf(leaf);
+        }
+    }
+
+    fn build(mut self) -> Rope {
+        self.flush();
+        self.b.build()
+    }
+}
+
+/// Builds a rope from formatted or streamed output, without collecting it
+/// in an intermediate `String`.
+///
+/// Text written through [`fmt::Write`], as with `write!`, or bytes written
+/// through [`io::Write`] are gathered into leaves as they arrive; call
+/// [`into_rope`] to finish. Bytes must be UTF-8, but a character may be
+/// split across writes: its leading bytes are held until the rest arrive.
+///
+/// [`into_rope`]: #method.into_rope
+pub struct RopeWriter {
+    acc: LeafAccumulator,
+    // the leading bytes of a character split across `io::Write` calls
+    partial: Vec<u8>,
+}
+
+impl RopeWriter {
+    pub fn new() -> RopeWriter {
+        RopeWriter { acc: LeafAccumulator::new(Rope::default()), partial: Vec::new() }
+    }
+
+    /// Finishes writing and returns the rope.
+    ///
+    /// If the bytes written ended partway through a character, that
+    /// incomplete character is replaced by U+FFFD REPLACEMENT CHARACTER.
+    pub fn into_rope(mut self) -> Rope {
+        if !self.partial.is_empty() {
+            self.acc.push_str("\u{FFFD}");
+        }
+        self.acc.build()
+    }
+}
+
+impl Default for RopeWriter {
+    fn default() -> RopeWriter {
+        RopeWriter::new()
+    }
+}
+
+impl fmt::Write for RopeWriter {
+    /// Appends `s`. Fails if a character written through `io::Write` is
+    /// still incomplete, since `s` cannot continue it.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if !self.partial.is_empty() {
+            return Err(fmt::Error);
+        }
+        self.acc.push_str(s);
+        Ok(())
+    }
+}
+
+impl io::Write for RopeWriter {
+    /// Appends the UTF-8 text in `buf`.
+    ///
+    /// An invalid sequence is an `InvalidData` error once the valid text
+    /// before it has been accepted.
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let invalid =
+            || io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8");
+        let mut consumed = 0;
+        // first complete a character left over from the previous write
+        while !self.partial.is_empty() && consumed < buf.len() {
+            self.partial.push(buf[consumed]);
+            consumed += 1;
+            match std::str::from_utf8(&self.partial) {
+                Ok(s) => {
+                    self.acc.push_str(s);
+                    self.partial.clear();
+                }
+                Err(e) if e.error_len().is_none() => (),
+                Err(_) => {
+                    self.partial.truncate(self.partial.len() - consumed);
+                    return Err(invalid());
+                }
+            }
+        }
+        let rest = &buf[consumed..];
+        match std::str::from_utf8(rest) {
+            Ok(s) => self.acc.push_str(s),
+            Err(e) => {
+                let valid = e.valid_up_to();
+                self.acc.push_str(std::str::from_utf8(&rest[..valid]).unwrap());
+                if e.error_len().is_some() {
+                    return if consumed + valid == 0 {
+                        Err(invalid())
+                    } else {
+                        Ok(consumed + valid)
+                    };
+                }
+                self.partial.extend_from_slice(&rest[valid..]);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Extend<char> for Rope {
+    /// Appends the chars to the rope.
+    ///
+    /// Time complexity: O(n) amortized in the number of chars added.
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        let mut acc = LeafAccumulator::new(std::mem::take(self));
+        let mut utf8 = [0; 4];
+        for c in iter {
+            acc.push_str(c.encode_utf8(&mut utf8));
+        }
+        *self = acc.build();
+    }
+}
+
+impl<'a> Extend<&'a str> for Rope {
+    /// Appends the strings to the rope.
+    ///
+    /// Time complexity: O(n) amortized in the number of bytes added.
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        let mut acc = LeafAccumulator::new(std::mem::take(self));
+        for s in iter {
+            acc.push_str(s);
+        }
+        *self = acc.build();
+    }
+}
+
+impl Extend<String> for Rope {
+    /// Appends the strings to the rope.
+    ///
+    /// Time complexity: O(n) amortized in the number of bytes added.
+    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
+        let mut acc = LeafAccumulator::new(std::mem::take(self));
+        for s in iter {
+            acc.push_str(&s);
+        }
+        *self = acc.build();
+    }
+}
+
+impl FromIterator<char> for Rope {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Rope {
+        let mut rope = Rope::default();
+        rope.extend(iter);
+        rope
+    }
+}
+
+impl<'a> FromIterator<&'a str> for Rope {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Rope {
+        let mut rope = Rope::default();
+        rope.extend(iter);
+        rope
+    }
+}
+
+impl FromIterator<String> for Rope {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Rope {
+        let mut rope = Rope::default();
+        rope.extend(iter);
+        rope
+    }
+}
+
 impl From<Rope> for String {
     // maybe explore grabbing leaf? would require api in tree
     fn from(r: Rope) -> String {
@@ -617,6 +1791,61 @@ impl fmt::Debug for Rope {
     }
 }
 
+/// Ropes compare equal when their contents are byte-for-byte equal,
+/// regardless of how the text is divided into leaves.
+impl PartialEq for Rope {
+    fn eq(&self, other: &Rope) -> bool {
+        self.len() == other.len() && self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Rope {}
+
+impl PartialOrd for Rope {
+    fn partial_cmp(&self, other: &Rope) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Ropes are ordered lexicographically by their bytes, the same as the
+/// `String`s they would convert to. The comparison walks both ropes a chunk
+/// at a time, without materializing either.
+impl Ord for Rope {
+    fn cmp(&self, other: &Rope) -> Ordering {
+        if self.ptr_eq(other) {
+            return Ordering::Equal;
+        }
+        let mut ours = self.iter_chunks(..).fuse();
+        let mut theirs = other.iter_chunks(..).fuse();
+        let mut a: &[u8] = &[];
+        let mut b: &[u8] = &[];
+        loop {
+            if a.is_empty() {
+                a = match ours.next() {
+                    Some(chunk) => chunk.as_bytes(),
+                    None => break,
+                };
+            }
+            if b.is_empty() {
+                b = match theirs.next() {
+                    Some(chunk) => chunk.as_bytes(),
+                    None => break,
+                };
+            }
+            let n = min(a.len(), b.len());
+            if let Some(i) = ne_idx(&a[..n], &b[..n]) {
+                return a[i].cmp(&b[i]);
+            }
+            a = &a[n..];
+            b = &b[n..];
+        }
+        // One side ran out; whichever has bytes left is the greater.
+        let a_done = a.is_empty() && ours.next().is_none();
+        let b_done = b.is_empty() && theirs.next().is_none();
+        a_done.cmp(&b_done).reverse()
+    }
+}
+
 impl Add for Rope {
     type Output = Rope;
     fn add(self, rhs: Rope) -> Rope {
@@ -656,6 +1885,31 @@ impl<'a> Cursor<'a, RopeInfo, String> {
         self.get_leaf().and_then(|(l, off)| l[off..].chars().next())
     }
 
+    /// Scans forward from the cursor a chunk at a time, until `f` returns an
+    /// offset to stop at.
+    ///
+    /// `f` is given each chunk along with the offset in the rope at which the
+    /// chunk begins. The first chunk is the rest of the current leaf; after
+    /// that the cursor moves with `next_leaf`, without descending from the
+    /// root again. When `f` returns `Some(offset)`, the cursor is set to that
+    /// offset, which is also returned. If the end of the rope is reached
+    /// first, returns `None` and leaves the cursor at the end.
+    pub fn scan_chunks_from<F>(&mut self, mut f: F) -> Option<usize>
+    where
+        F: FnMut(&str, usize) -> Option<usize>,
+    {
+        let (mut leaf, mut offset) = self.get_leaf()?;
+        loop {
+            if let Some(found) = f(&leaf[offset..], self.pos()) {
+                self.set(found);
+                return Some(found);
+            }
+            let (next, next_offset) = self.next_leaf()?;
+            leaf = next;
+            offset = next_offset;
+        }
+    }
+
     pub fn next_grapheme(&mut self) -> Option<usize> {
         let (mut l, mut offset) = self.get_leaf()?;
         let mut pos = self.pos();
@@ -711,13 +1965,171 @@ impl<'a> Cursor<'a, RopeInfo, String> {
         }
         prev_boundary.unwrap_or(None)
     }
+
+    /// Returns an iterator over the grapheme clusters from the cursor
+    /// position onward, as `(start_offset, cluster)` pairs.
+    ///
+    /// A segmenter is fed one leaf at a time, so iterating the whole rope is
+    /// linear, and clusters that straddle leaves are stitched together. The
+    /// cursor should start on a grapheme boundary; it follows the iteration
+    /// and is left after the last cluster returned.
+    pub fn grapheme_iter(&mut self) -> Graphemes<'_, 'a> {
+        let segmenter = GraphemeCursor::new(self.pos(), self.total_len(), true);
+        Graphemes { start: self.pos(), cursor: self, segmenter }
+    }
+}
+
+/// An iterator over the grapheme clusters of a rope, created by
+/// [`Cursor::grapheme_iter`].
+///
+/// [`Cursor::grapheme_iter`]: struct.Cursor.html#method.grapheme_iter
+pub struct Graphemes<'c, 'a> {
+    cursor: &'c mut Cursor<'a, RopeInfo, String>,
+    segmenter: GraphemeCursor,
+    start: usize,
+}
+
+impl<'c, 'a> Iterator for Graphemes<'c, 'a> {
+    type Item = (usize, String);
+
+    fn next(&mut self) -> Option<(usize, String)> {
+        let start = self.start;
+        let (mut leaf, offset) = self.cursor.get_leaf()?;
+        let mut leaf_start = self.cursor.pos() - offset;
+        let mut restarted = false;
+        loop {
+            match self.segmenter.next_boundary(leaf, leaf_start) {
+                Ok(Some(end)) => {
+                    let cluster = if start >= leaf_start {
+                        leaf[start - leaf_start..end - leaf_start].to_owned()
+                    } else {
+                        self.cursor.root().slice_to_cow(start..end).into_owned()
+                    };
+                    self.cursor.set(end);
+                    self.start = end;
+                    return Some((start, cluster));
+                }
+                Ok(None) => return None,
+                Err(GraphemeIncomplete::NextChunk) if !restarted => {
+                    // A segmenter resumed in a new chunk can misjudge pairs of
+                    // regional indicators, so start a fresh one at the cluster
+                    // start before crossing leaves, as `next_grapheme` does.
+                    let total_len = self.cursor.total_len();
+                    self.segmenter = GraphemeCursor::new(start, total_len, true);
+                    restarted = true;
+                }
+                Err(GraphemeIncomplete::NextChunk) => {
+                    leaf = self.cursor.next_leaf()?.0;
+                    leaf_start = self.cursor.pos();
+                }
+                Err(GraphemeIncomplete::PreContext(context_end)) => {
+                    let mut context = Cursor::new(self.cursor.root(), context_end);
+                    let (prev, prev_offset) = context.prev_leaf()?;
+                    self.segmenter.provide_context(prev, context.pos() - prev_offset);
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+// line iterators
+
+pub struct LinesRaw<'a> {
+    inner: ChunkIter<'a>,
+    fragment: &'a str,
+}
+
+/// An iterator over the raw lines of a rope, from last to first.
+pub struct LinesRawRev<'a> {
+    cursor: Cursor<'a, RopeInfo, String>,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Iterator for LinesRawRev<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        if self.end <= self.start {
+            return None;
+        }
+        self.cursor.set(self.end);
+        let line_start = match self.cursor.prev::<LinesMetric>() {
+            Some(pos) if pos > self.start => pos,
+            _ => self.start,
+        };
+        let line = self.cursor.root().slice_to_cow(line_start..self.end);
+        self.end = line_start;
+        Some(line)
+    }
+}
+
+/// An iterator over the paragraphs of a rope; see [`Rope::paragraphs`].
+///
+/// [`Rope::paragraphs`]: type.Rope.html#method.paragraphs
+pub struct Paragraphs<'a> {
+    rope: &'a Rope,
+    lines: Peekable<LinesRaw<'a>>,
+    offset: usize,
+}
+
+impl<'a> Iterator for Paragraphs<'a> {
+    type Item = (Range<usize>, Cow<'a, str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let is_blank = |line: &str| line.trim().is_empty();
+        while let Some(line) = self.lines.next_if(|line| is_blank(line)) {
+            self.offset += line.len();
+        }
+        let start = self.offset;
+        while let Some(line) = self.lines.next_if(|line| !is_blank(line)) {
+            self.offset += line.len();
+        }
+        if self.offset == start {
+            return None;
+        }
+        Some((start..self.offset, self.rope.slice_to_cow(start..self.offset)))
+    }
+}
+
+/// An iterator over the line intervals of a rope; see [`Rope::line_intervals`].
+///
+/// [`Rope::line_intervals`]: type.Rope.html#method.line_intervals
+pub struct LineIntervals<'a> {
+    cursor: Cursor<'a, RopeInfo, String>,
+    line: usize,
+    offset: usize,
+    end: usize,
+    include_ending: bool,
 }
 
-// line iterators
+impl<'a> Iterator for LineIntervals<'a> {
+    type Item = (usize, Interval);
 
-pub struct LinesRaw<'a> {
-    inner: ChunkIter<'a>,
-    fragment: &'a str,
+    fn next(&mut self) -> Option<(usize, Interval)> {
+        if self.offset >= self.end {
+            return None;
+        }
+        let start = self.offset;
+        self.cursor.set(start);
+        let line_end = match self.cursor.next::<LinesMetric>() {
+            Some(pos) if pos <= self.end => pos,
+            _ => self.end,
+        };
+        let mut content_end = line_end;
+        let rope = self.cursor.root();
+        if !self.include_ending && rope.byte_at(content_end - 1) == b'\n' {
+            content_end -= 1;
+            if content_end > start && rope.byte_at(content_end - 1) == b'\r' {
+                content_end -= 1;
+            }
+        }
+        let line = self.line;
+        self.line += 1;
+        self.offset = line_end;
+        Some((line, Interval::new(start, content_end)))
+    }
 }
 
 fn cow_append<'a>(a: Cow<'a, str>, b: &'a str) -> Cow<'a, str> {
@@ -802,6 +2214,36 @@ mod tests {
         assert_eq!("herald", String::from(a));
     }
 
+    #[test]
+    fn extend_chars() {
+        let mut a = Rope::from("hello ");
+        a.extend("world".chars());
+        assert_eq!("hello world", String::from(&a));
+
+        let expected: String =
+            (0..1_000_000).map(|i| if i % 7 == 0 { '\u{e9}' } else { 'a' }).collect();
+        let mut big = Rope::default();
+        big.extend(expected.chars());
+        assert_eq!(expected.len(), big.len());
+        assert_eq!(expected, String::from(&big));
+    }
+
+    #[test]
+    fn extend_strs() {
+        let long = "x".repeat(MAX_LEAF * 3 + 17);
+        let pieces = ["a\n", "", long.as_str(), "b", "\u{1f600}"];
+        let expected: String = pieces.concat();
+
+        let mut a = Rope::default();
+        a.extend(pieces.iter().copied());
+        assert_eq!(expected, String::from(&a));
+        assert_eq!(2, a.measure::<LinesMetric>() + 1);
+
+        let mut b = Rope::from("prefix");
+        b.extend(pieces.iter().map(|s| s.to_string()));
+        assert_eq!(format!("prefix{}", expected), String::from(&b));
+    }
+
     #[test]
     fn lines_raw_small() {
         let a = Rope::from("a\nb\nc");
@@ -845,6 +2287,67 @@ mod tests {
         assert_eq!(String::from(&a).lines().collect::<Vec<_>>(), a.lines(..).collect::<Vec<_>>());
     }
 
+    #[test]
+    fn slice_str_borrows_within_leaf() {
+        let small = Rope::from("hello world");
+        assert!(matches!(small.slice_str(1..5), Cow::Borrowed("ello")));
+        assert!(matches!(small.slice_str(..), Cow::Borrowed("hello world")));
+
+        let s = "0123456789".repeat(500);
+        let big = Rope::from(&s);
+        let (first_leaf, _) = Cursor::new(&big, 0).get_leaf().unwrap();
+        let edge = first_leaf.len();
+        assert!(edge < s.len());
+        assert!(matches!(big.slice_str(edge - 5..edge), Cow::Borrowed(_)));
+        let across = big.slice_str(edge - 5..edge + 5);
+        assert!(matches!(across, Cow::Owned(_)));
+        assert_eq!(&s[edge - 5..edge + 5], across);
+    }
+
+    #[test]
+    fn push_str_small_appends() {
+        let mut a = Rope::from("hello");
+        a.push_str("");
+        a.push_str(" world");
+        assert_eq!("hello world", String::from(&a));
+
+        let mut expected = String::new();
+        let mut b = Rope::default();
+        for i in 0..10_000 {
+            let s = if i % 100 == 99 { "\n" } else { "x" };
+            b.push_str(s);
+            expected.push_str(s);
+        }
+        assert_eq!(expected, String::from(&b));
+        assert_eq!(99, b.measure::<LinesMetric>() - 1);
+        let mut leaves = 0;
+        let mut cursor = Cursor::new(&b, 0);
+        while cursor.get_leaf().is_some() {
+            leaves += 1;
+            cursor.next_leaf();
+        }
+        assert!(leaves <= expected.len() / MIN_LEAF + 1, "{} leaves", leaves);
+
+        let long = "y".repeat(MAX_LEAF * 2 + 3);
+        b.push_str(&long);
+        expected.push_str(&long);
+        assert_eq!(expected, String::from(&b));
+    }
+
+    #[test]
+    fn first_lines() {
+        let a = Rope::from("one\ntwo\r\nthree\nfour");
+        assert_eq!((vec![Cow::from("one"), Cow::from("two")], true), a.first_lines(2));
+        assert_eq!((vec![], true), a.first_lines(0));
+        let (lines, truncated) = a.first_lines(4);
+        assert_eq!(vec!["one", "two", "three", "four"], lines);
+        assert!(!truncated);
+
+        let short = Rope::from("a\nb\n");
+        assert_eq!((vec![Cow::from("a"), Cow::from("b")], false), short.first_lines(10));
+        assert_eq!((vec![], false), Rope::from("").first_lines(3));
+    }
+
     #[test]
     fn lines_med() {
         let mut a = String::new();
@@ -1226,6 +2729,907 @@ mod tests {
         assert!(long_text.len() > 1024);
         assert_eq!(cow, Cow::Borrowed(&long_text[..500]));
     }
+
+    #[test]
+    fn count_char() {
+        let s = "a,b\t😀,c,\t😀".repeat(500);
+        let r = Rope::from(&s);
+        for &ch in &[',', '\t', '😀', 'z'] {
+            assert_eq!(r.count_char(ch), s.chars().filter(|&c| c == ch).count());
+        }
+        assert_eq!(Rope::from("").count_char(','), 0);
+    }
+
+    #[test]
+    fn from_iter_pieces() {
+        assert_eq!(vec!["a", "b", "c"].into_iter().collect::<Rope>(), Rope::from("abc"));
+        assert_eq!("xyz".chars().collect::<Rope>(), Rope::from("xyz"));
+
+        let lines: Vec<String> = (0..2000).map(|i| format!("line {}\n", i)).collect();
+        let rope = lines.iter().cloned().collect::<Rope>();
+        assert_eq!(String::from(&rope), lines.concat());
+        assert_eq!(rope.measure::<LinesMetric>(), 2000);
+    }
+
+    #[test]
+    fn strip_bom() {
+        let (stripped, had_bom) = Rope::from("\u{FEFF}hello").strip_bom();
+        assert!(had_bom);
+        assert_eq!(String::from(stripped), "hello");
+
+        let (stripped, had_bom) = Rope::from("\u{FEFF}").strip_bom();
+        assert!(had_bom);
+        assert!(stripped.is_empty());
+
+        for text in &["hello", "abc", "\u{FEFE}", "ab", ""] {
+            let (stripped, had_bom) = Rope::from(text).strip_bom();
+            assert!(!had_bom);
+            assert_eq!(String::from(stripped), *text);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn push_leaf_exact_too_long() {
+        TreeBuilder::<RopeInfo, String>::new().push_leaf_exact("x".repeat(MAX_LEAF + 1));
+    }
+
+    #[test]
+    fn push_leaf_exact_keeps_leaves() {
+        let leaf_lens = |rope: &Rope| {
+            let mut lens = Vec::new();
+            let mut cursor = Cursor::new(rope, 0);
+            while let Some((leaf, _)) = cursor.get_leaf() {
+                lens.push(leaf.len());
+                if cursor.next_leaf().is_none() {
+                    break;
+                }
+            }
+            lens
+        };
+
+        let sizes = [MIN_LEAF, MAX_LEAF, MIN_LEAF + 1, MAX_LEAF - 1, MIN_LEAF];
+        let mut b = TreeBuilder::<RopeInfo, String>::new();
+        for n in 0..20 {
+            b.push_leaf_exact("x".repeat(sizes[n % sizes.len()]));
+        }
+        let expected: Vec<usize> = (0..20).map(|n| sizes[n % sizes.len()]).collect();
+        assert_eq!(leaf_lens(&b.build()), expected);
+
+        // a single short leaf is a valid tree on its own
+        let mut b = TreeBuilder::<RopeInfo, String>::new();
+        b.push_leaf_exact("z".repeat(3));
+        assert_eq!(leaf_lens(&b.build()), vec![3]);
+    }
+
+    #[test]
+    fn lines_raw_rev_matches_forward() {
+        let long_line = "x".repeat(MAX_LEAF * 2);
+        let texts = [
+            String::new(),
+            "\n".to_owned(),
+            "one\ntwo\nthree".to_owned(),
+            "one\ntwo\nthree\n".to_owned(),
+            "\n\nblank\n\n".to_owned(),
+            format!("a\n{}\nb\n{}", long_line, long_line),
+        ];
+        for text in &texts {
+            let rope = Rope::from(text);
+            let mut forward: Vec<_> = rope.lines_raw(..).collect();
+            forward.reverse();
+            let reverse: Vec<_> = rope.lines_raw_rev(..).collect();
+            assert_eq!(reverse, forward);
+        }
+
+        let rope = Rope::from("one\ntwo\nthree\n");
+        let reverse: Vec<_> = rope.lines_raw_rev(2..10).collect();
+        assert_eq!(reverse, vec!["th", "two\n", "e\n"]);
+    }
+
+    #[test]
+    fn indentation_of_line() {
+        let rope = Rope::from("fn main() {\n    let x;\n\tlet y;\n \t  z\n   \n\n    ");
+        let indents: Vec<_> = (0..7).map(|line| rope.indentation_of_line(line)).collect();
+        assert_eq!(
+            indents,
+            vec![
+                (0, "".into()),
+                (4, "    ".into()),
+                (1, "\t".into()),
+                (4, " \t  ".into()),
+                (3, "   ".into()),
+                (0, "".into()),
+                (4, "    ".into()),
+            ]
+        );
+
+        let long_indent = " ".repeat(MAX_LEAF * 3);
+        let rope = Rope::from(format!("x\n{}y\n", long_indent));
+        assert_eq!(rope.indentation_of_line(1), (long_indent.len(), long_indent.as_str().into()));
+    }
+
+    #[test]
+    fn paragraphs() {
+        let text = "\n  \nFirst para\nstill first\n\n\n \t\nSecond\n\nThird\nends here";
+        let rope = Rope::from(text);
+        let paras: Vec<_> = rope.paragraphs(..).collect();
+        let expected: Vec<(Range<usize>, &str)> = vec![
+            (4..27, "First para\nstill first\n"),
+            (32..39, "Second\n"),
+            (40..55, "Third\nends here"),
+        ];
+        assert_eq!(paras.len(), expected.len());
+        for ((range, para), (exp_range, exp_para)) in paras.into_iter().zip(expected) {
+            assert_eq!(range, exp_range);
+            assert_eq!(para, exp_para);
+            assert_eq!(&text[range], exp_para);
+        }
+
+        let rope = Rope::from("para\n\n\n");
+        let paras: Vec<_> = rope.paragraphs(..).collect();
+        assert_eq!(paras, vec![(0..5, Cow::from("para\n"))]);
+        assert_eq!(Rope::from(" \n\n").paragraphs(..).count(), 0);
+        assert_eq!(Rope::from("").paragraphs(..).count(), 0);
+    }
+
+    #[test]
+    fn truncate() {
+        let s = "a".repeat(MAX_LEAF) + &"b".repeat(MAX_LEAF) + "é";
+        let mut rope = Rope::from(&s);
+        let first_leaf = rope.iter_chunks(..).next().unwrap();
+        let (first_ptr, cut) = (first_leaf.as_ptr(), first_leaf.len() + MIN_LEAF);
+
+        rope.truncate(s.len() + 10);
+        assert_eq!(String::from(&rope), s);
+
+        // cut inside the second leaf, leaving a piece big enough to stand alone
+        rope.truncate(cut);
+        assert_eq!(String::from(&rope), &s[..cut]);
+        assert_eq!(rope.iter_chunks(..).next().unwrap().as_ptr(), first_ptr);
+
+        rope.truncate(0);
+        assert!(rope.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn truncate_inside_char() {
+        let mut rope = Rope::from("aé");
+        rope.truncate(2);
+    }
+
+    #[test]
+    fn replace_char() {
+        let rope = Rope::from("a\u{A0}b\u{A0}\u{A0}c");
+        let delta = rope.replace_char('\u{A0}', ' ');
+        assert_eq!(delta.base_len, rope.len());
+        assert_eq!(delta.new_document_len(), rope.len() - 3);
+        assert_eq!(String::from(delta.apply(&rope)), "a b  c");
+
+        let rope = Rope::from("\u{201C}quoted\u{201D}");
+        let delta = rope.replace_char('\u{201C}', '\u{201D}');
+        assert_eq!(String::from(delta.apply(&rope)), "\u{201D}quoted\u{201D}");
+
+        // occurrences in every leaf are found, other text is copied
+        let s = "x".repeat(MAX_LEAF) + "\u{A0}" + &"y".repeat(MAX_LEAF) + "\u{A0}";
+        let rope = Rope::from(&s);
+        let delta = rope.replace_char('\u{A0}', '_');
+        assert_eq!(String::from(delta.apply(&rope)), s.replace('\u{A0}', "_"));
+        assert!(rope.replace_char('z', 'q').is_identity());
+    }
+
+    #[test]
+    fn grapheme_iter_matches_segmentation() {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        // leaves split an accented letter and a pair of regional indicators
+        let flags = "\u{1f1fa}\u{1f1f8}".repeat(MIN_LEAF / 8 + 1);
+        let mut b = TreeBuilder::new();
+        b.push_leaf_exact("a".repeat(MIN_LEAF - 1) + "e");
+        b.push_leaf_exact(format!("\u{301}{}\u{1f1fa}", flags));
+        b.push_leaf_exact(format!("\u{1f1f8}{}\r", "b".repeat(MIN_LEAF)));
+        b.push_leaf_exact(format!("\n{}", "\u{915}\u{94d}\u{937}".repeat(MIN_LEAF / 9 + 1)));
+        let rope: Rope = b.build();
+        let text = String::from(&rope);
+        let expected =
+            text.grapheme_indices(true).map(|(i, g)| (i, g.to_owned())).collect::<Vec<_>>();
+
+        let mut cursor = Cursor::new(&rope, 0);
+        assert_eq!(cursor.grapheme_iter().collect::<Vec<_>>(), expected);
+        assert_eq!(cursor.pos(), rope.len());
+
+        // starting mid-rope needs context from earlier leaves
+        for (ix, &(offset, _)) in expected.iter().enumerate().step_by(7) {
+            let mut cursor = Cursor::new(&rope, offset);
+            assert_eq!(cursor.grapheme_iter().collect::<Vec<_>>(), &expected[ix..]);
+        }
+        assert_eq!(Cursor::new(&Rope::from(""), 0).grapheme_iter().next(), None);
+    }
+
+    #[test]
+    fn byte_len_of_lines() {
+        let a = Rope::from("one\ntwo\r\n\nlast");
+        assert_eq!(a.byte_len_of_lines(0..1), 4);
+        assert_eq!(a.byte_len_of_lines(1..3), 6);
+        // the final line has no trailing newline
+        assert_eq!(a.byte_len_of_lines(3..4), 4);
+        assert_eq!(a.byte_len_of_lines(0..4), a.len());
+        assert_eq!(a.byte_len_of_lines(2..2), 0);
+
+        let b = Rope::from("one\ntwo\n");
+        assert_eq!(b.byte_len_of_lines(1..2), 4);
+        assert_eq!(b.byte_len_of_lines(0..3), b.len());
+    }
+
+    #[test]
+    fn snap_to_grapheme_boundary() {
+        // a family emoji is four codepoints joined by three ZWJs
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+        let text = format!("ab{}e\u{301}x", family);
+        let a = Rope::from(&text);
+        let (start, end) = (2, 2 + family.len());
+        for offset in start + 1..end {
+            assert_eq!(a.floor_grapheme_boundary(offset), start);
+            assert_eq!(a.ceil_grapheme_boundary(offset), end);
+        }
+        for &offset in &[0, 1, start, end, end + 3, a.len()] {
+            assert_eq!(a.floor_grapheme_boundary(offset), offset);
+            assert_eq!(a.ceil_grapheme_boundary(offset), offset);
+        }
+        // inside the combining sequence
+        assert_eq!(a.floor_grapheme_boundary(end + 1), end);
+        assert_eq!(a.ceil_grapheme_boundary(end + 2), end + 3);
+    }
+
+    #[test]
+    fn trailing_whitespace_spans() {
+        let a = Rope::from("one  \ntwo\t \r\nclean\n \t\nlast  ");
+        assert_eq!(a.trailing_whitespace_spans(), vec![3..5, 9..11, 19..21, 26..28]);
+        assert!(Rope::from("a\nb\r\n\n").trailing_whitespace_spans().is_empty());
+        assert_eq!(Rope::from("é \u{A0} ").trailing_whitespace_spans(), vec![5..6]);
+    }
+
+    #[test]
+    fn lower_bound_by_measure() {
+        let text = "first\nsecond line\n\nlast".repeat(100);
+        let a = Rope::from(&text);
+        let lines = a.measure::<LinesMetric>();
+        for target in 0..=lines {
+            let offset = a.lower_bound_by_measure::<LinesMetric>(target);
+            assert_eq!(offset, a.count_base_units::<LinesMetric>(target));
+            assert!(a.count::<LinesMetric>(offset) >= target);
+            assert!(offset == 0 || a.count::<LinesMetric>(offset - 1) < target);
+        }
+        assert_eq!(a.lower_bound_by_measure::<LinesMetric>(lines + 1), a.len());
+        assert_eq!(a.lower_bound_by_measure::<LinesMetric>(usize::MAX), a.len());
+        assert_eq!(Rope::from("").lower_bound_by_measure::<LinesMetric>(3), 0);
+    }
+
+    #[test]
+    fn utf16_round_trip() {
+        let text = "plain \u{1F600} astral \u{10348} and \u{E9}\n".repeat(80);
+        let a = Rope::from(&text);
+        let mut units = vec![0x41];
+        a.encode_utf16_into(&mut units);
+        assert_eq!(&units[1..], &text.encode_utf16().collect::<Vec<_>>()[..]);
+        assert_eq!(units.len() - 1, a.measure::<Utf16CodeUnitsMetric>());
+
+        let b = Rope::from_utf16(&units[1..]).unwrap();
+        assert_eq!(String::from(&b), text);
+        assert_eq!(b.measure::<Utf16CodeUnitsMetric>(), units.len() - 1);
+        assert_eq!(String::from(Rope::from_utf16(&[]).unwrap()), "");
+
+        let lone = [0x61, 0xD83D, 0x62];
+        let err = Rope::from_utf16(&lone).unwrap_err();
+        assert_eq!(err.unpaired_surrogate(), 0xD83D);
+    }
+
+    #[test]
+    fn byte_char_round_trip() {
+        let text = "ascii 漢字 \u{1F600}\u{1F3FD} mixed\n".repeat(60);
+        let a = Rope::from(&text);
+        assert_eq!(a.measure::<CharsMetric>(), text.chars().count());
+        for (ch, (byte, c)) in text.char_indices().enumerate() {
+            assert_eq!(a.byte_to_char(byte), ch);
+            assert_eq!(a.char_to_byte(ch), byte);
+            // offsets inside a character snap down to it
+            for inner in byte + 1..byte + c.len_utf8() {
+                assert_eq!(a.byte_to_char(inner), ch);
+            }
+        }
+        let total = text.chars().count();
+        assert_eq!(a.byte_to_char(a.len()), total);
+        assert_eq!(a.char_to_byte(total), a.len());
+        assert_eq!(a.char_to_byte(total + 10), a.len());
+    }
+
+    #[test]
+    fn longest_common_span_finds_shared_middle() {
+        let shared = "fn renamed_helper(argument: &str) -> usize { argument.len() * 2 }";
+        let a = Rope::from(format!("{}{}{}", "header one\n".repeat(80), shared, "\ntail α\n"));
+        let b = Rope::from(format!("{}{}{}", "preamble ∑\n", shared, "\n".repeat(3)));
+        let (ra, rb) = Rope::longest_common_span(&a, &b).unwrap();
+        assert!(ra.len() >= shared.len());
+        assert_eq!(a.slice_to_cow(ra.clone()), b.slice_to_cow(rb.clone()));
+        assert!(a.slice_to_cow(ra).contains(shared));
+
+        let c = Rope::from("nothing in common here");
+        assert_eq!(Rope::longest_common_span(&a, &c), None);
+        assert_eq!(
+            Rope::longest_common_span_with_window(&c, &c, 4),
+            Some((0..c.len(), 0..c.len()))
+        );
+    }
+
+    #[test]
+    fn ord_matches_string_ordering() {
+        let long = "abcdefghij".repeat(300);
+        let mut b = TreeBuilder::new();
+        b.push_leaves(
+            long.as_bytes().chunks(MIN_LEAF + 3).map(|c| String::from_utf8(c.to_vec()).unwrap()),
+        );
+        let uneven = b.build();
+        let even = Rope::from(&long);
+        assert_eq!(even.cmp(&uneven), Ordering::Equal);
+        assert_eq!(even, uneven);
+
+        let mut texts = vec![
+            long.clone(),
+            long[..2000].to_string(),
+            format!("{}z", &long[..1500]),
+            format!("{}a", &long[..2999]),
+            String::new(),
+            "b".to_string(),
+        ];
+        let ropes: Vec<Rope> = texts.iter().map(Rope::from).collect();
+        for (x, rx) in texts.iter().zip(&ropes) {
+            for (y, ry) in texts.iter().zip(&ropes) {
+                assert_eq!(
+                    rx.cmp(ry),
+                    x.cmp(y),
+                    "{:?} vs {:?}",
+                    &x[..x.len().min(8)],
+                    &y[..y.len().min(8)]
+                );
+            }
+        }
+        let prefix = Rope::from(&long[..2000]);
+        assert!(prefix < uneven);
+
+        let mut sorted = ropes.clone();
+        sorted.sort();
+        texts.sort();
+        assert_eq!(sorted.iter().map(String::from).collect::<Vec<_>>(), texts);
+    }
+
+    #[test]
+    fn is_ascii_tracks_edits() {
+        let mut rope = Rope::from("plain text\n".repeat(300));
+        assert!(rope.is_ascii());
+        assert!(Rope::from("").is_ascii());
+
+        rope.edit(1500..1500, "é");
+        assert!(!rope.is_ascii());
+        rope.edit(1500..1502, "");
+        assert!(rope.is_ascii());
+
+        assert!(!Rope::from("naïve").is_ascii());
+        assert!(Rope::from("naïve").slice(..2).is_ascii());
+    }
+
+    #[test]
+    fn graphemes_rev_zwj_family() {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}\u{200d}\u{1f466}";
+        let text = format!("say hi e\u{301} {}", family);
+        let rope = Rope::from(text.as_str());
+        let clusters = rope.graphemes_rev(..).collect::<Vec<_>>();
+        assert_eq!(clusters[0], (text.len() - family.len(), family.to_owned()));
+        let mut expected =
+            text.grapheme_indices(true).map(|(i, g)| (i, g.to_owned())).collect::<Vec<_>>();
+        expected.reverse();
+        assert_eq!(clusters, expected);
+
+        // the family straddles a leaf boundary, after the woman
+        let split = "\u{1f468}\u{200d}\u{1f469}".len();
+        let mut b = TreeBuilder::new();
+        b.push_leaf_exact("x".repeat(MIN_LEAF) + &family[..split]);
+        b.push_leaf_exact(family[split..].to_owned() + &"y".repeat(MIN_LEAF));
+        let rope: Rope = b.build();
+        let end = MIN_LEAF + family.len();
+        let mut rev = rope.graphemes_rev(..end);
+        assert_eq!(rev.next(), Some((MIN_LEAF, family.to_owned())));
+        assert_eq!(rev.next(), Some((MIN_LEAF - 1, "x".to_owned())));
+        assert_eq!(rope.graphemes_rev(MIN_LEAF + 4..end).collect::<Vec<_>>().len(), 1);
+    }
+
+    #[test]
+    fn detect_line_ending() {
+        let mut text = "windows line\r\n".repeat(30);
+        text.insert_str(100, "stray\n");
+        text.push_str("unix\n");
+        assert_eq!(Rope::from(text.as_str()).detect_line_ending(), LineEnding::CrLf);
+
+        assert_eq!(Rope::from("a\nb\r\nc\n").detect_line_ending(), LineEnding::Lf);
+        assert_eq!(Rope::from("a\r\nb\n").detect_line_ending(), LineEnding::Lf);
+        assert_eq!(Rope::from("no newline\r").detect_line_ending(), LineEnding::Lf);
+        assert_eq!(Rope::from("").detect_line_ending(), LineEnding::Lf);
+
+        // only the first 64 lines are sampled
+        let late = format!("{}{}", "x\r\n".repeat(64), "y\n".repeat(100));
+        assert_eq!(Rope::from(late).detect_line_ending(), LineEnding::CrLf);
+    }
+
+    #[test]
+    fn apply_edits_matches_sequential() {
+        let base = Rope::from("0123456789abcdef".repeat(500));
+        let edits: Vec<(Interval, Rope)> = (0..100)
+            .map(|i| {
+                let start = i * 80 + (i % 7);
+                let replacement = match i % 3 {
+                    0 => String::new(),
+                    1 => format!("<{}>", i),
+                    _ => "é".repeat(i),
+                };
+                (Interval::new(start, start + i % 11), Rope::from(replacement))
+            })
+            .collect();
+
+        let mut batched = base.clone();
+        batched.apply_edits(&edits);
+        let mut sequential = base.clone();
+        for (iv, new) in edits.iter().rev() {
+            sequential.edit(*iv, new.clone());
+        }
+        assert_eq!(String::from(&batched), String::from(&sequential));
+
+        let mut unchanged = base.clone();
+        unchanged.apply_edits(&[]);
+        assert_eq!(unchanged, base);
+    }
+
+    #[test]
+    #[should_panic(expected = "sorted and non-overlapping")]
+    fn apply_edits_overlapping() {
+        let mut rope = Rope::from("hello world");
+        rope.apply_edits(&[
+            (Interval::new(0, 5), Rope::from("a")),
+            (Interval::new(4, 6), Rope::from("b")),
+        ]);
+    }
+
+    #[test]
+    fn apply_edits_reuses_untouched_leaves() {
+        fn leaves(node: &Rope, out: &mut Vec<Rope>) {
+            if node.is_leaf() {
+                out.push(node.clone());
+            } else {
+                for child in node.get_children() {
+                    leaves(child, out);
+                }
+            }
+        }
+        let base = Rope::from("0123456789abcdef".repeat(20_000));
+        let edits: Vec<(Interval, Rope)> = (0..20)
+            .map(|i| (Interval::new(i * 16_000 + 3, i * 16_000 + 9), Rope::from("edit")))
+            .collect();
+        let mut edited = base.clone();
+        edited.apply_edits(&edits);
+
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        leaves(&base, &mut before);
+        leaves(&edited, &mut after);
+        // Only the leaves around each edit are rebuilt; the rest are shared.
+        let shared = before.iter().filter(|old| after.iter().any(|new| new.ptr_eq(old))).count();
+        assert!(shared >= before.len() - 2 * edits.len(), "{} of {} shared", shared, before.len());
+        assert!(after.len() <= before.len() + edits.len());
+    }
+
+    #[test]
+    fn replace_reindented() {
+        let rope = Rope::from("fn main() {\n    old_one();\n    old_two();\n}\n");
+        let start = rope.offset_of_line(1) + 4;
+        let end = rope.offset_of_line(3) - 1;
+        let delta = rope.replace_reindented(
+            Interval::new(start, end),
+            "let a = 1;\nlet b = 2;\nrun(a, b);",
+            "    ",
+        );
+        assert_eq!(
+            String::from(delta.apply(&rope)),
+            "fn main() {\n    let a = 1;\n    let b = 2;\n    run(a, b);\n}\n"
+        );
+
+        // blank lines and the remainder after a final newline stay unindented
+        let delta =
+            rope.replace_reindented(Interval::new(start, end + 1), "a();\r\n\r\nb();\n", "\t");
+        assert_eq!(String::from(delta.apply(&rope)), "fn main() {\n    a();\r\n\r\n\tb();\n}\n");
+    }
+
+    #[test]
+    fn newline_and_display_line_counts() {
+        let cases = [
+            ("", 0, 0),
+            ("a", 0, 1),
+            ("a\n", 1, 1),
+            ("a\nb", 1, 2),
+            ("\n", 1, 1),
+            ("a\r\n\n", 2, 2),
+        ];
+        for &(text, newlines, lines) in &cases {
+            let rope = Rope::from(text);
+            assert_eq!(rope.newline_count(), newlines, "{:?}", text);
+            assert_eq!(rope.display_line_count(), lines, "{:?}", text);
+        }
+        let big = Rope::from("line\n".repeat(1000));
+        assert_eq!((big.newline_count(), big.display_line_count()), (1000, 1000));
+    }
+
+    #[test]
+    fn iter_chunks_max() {
+        let text = "ab😀c€de🎉🎉x".repeat(150);
+        let rope = Rope::from(text.as_str());
+        let chunks: Vec<_> = rope.iter_chunks_max(.., 4).collect();
+        assert!(chunks.iter().all(|c| !c.is_empty() && c.len() <= 4));
+        assert_eq!(chunks.concat(), text);
+
+        let mid: String = rope.iter_chunks_max(6..1203, 7).collect();
+        assert_eq!(mid, &text[6..1203]);
+
+        // a scalar wider than `max` comes out whole
+        let narrow = Rope::from("a😀b");
+        let narrow: Vec<_> = narrow.iter_chunks_max(.., 2).collect();
+        assert_eq!(narrow, vec!["a", "😀", "b"]);
+    }
+
+    #[test]
+    fn grapheme_count_across_leaves() {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        let filler = "x".repeat(MIN_LEAF);
+        let joints: &[(&str, &str)] = &[
+            ("e", "\u{301}"),
+            ("\r", "\n"),
+            ("\u{1f468}\u{200d}", "\u{1f469}"),
+            ("\u{1f1eb}", "\u{1f1f7}"),
+            ("\u{1f1eb}\u{1f1f7}\u{1f1e9}", "\u{1f1ea}\u{1f1eb}"),
+            ("a", "b"),
+        ];
+        for &(left, right) in joints {
+            let mut b = TreeBuilder::new();
+            b.push_leaf(format!("{}{}", filler, left));
+            b.push_leaf(format!("{}{}", right, filler));
+            let rope = b.build();
+            let text = String::from(&rope);
+            assert_eq!(rope.grapheme_count(), text.graphemes(true).count(), "{:?}", left);
+        }
+
+        let mut rope = Rope::from("e\u{301}\r\n\u{1f1eb}\u{1f1f7}".repeat(200));
+        let mut text = String::from(&rope);
+        assert_eq!(rope.grapheme_count(), 600);
+        for &(start, end, new) in &[(1959, 1959, "\n"), (1043, 1045, "\u{308}"), (0, 1, "")] {
+            rope.edit(start..end, new);
+            text.replace_range(start..end, new);
+            assert_eq!(rope.grapheme_count(), text.graphemes(true).count());
+        }
+        assert_eq!(Rope::from("").grapheme_count(), 0);
+
+        // a run of regional indicators longer than a leaf is counted by
+        // iterating the clusters
+        let flags = Rope::from(format!("x{}", "\u{1f1eb}".repeat(301)));
+        assert!(flags.info().graphemes.count().is_none());
+        assert_eq!(flags.grapheme_count(), 1 + 151);
+    }
+
+    #[test]
+    fn content_hash_ignores_layout() {
+        let text = "a fairly ordinary line\n".repeat(100);
+        let flat = Rope::from(&text);
+
+        let mut b = TreeBuilder::new();
+        b.push_leaf(text[..MIN_LEAF].to_owned());
+        b.push_leaf(text[MIN_LEAF..MIN_LEAF * 3].to_owned());
+        b.push_leaf(text[MIN_LEAF * 3..].to_owned());
+        let built: Rope = b.build();
+
+        let mut edited = Rope::from(&text[..1000]);
+        edited.edit(1000..1000, &text[1000..]);
+
+        assert_eq!(flat.content_hash(), built.content_hash());
+        assert_eq!(flat.content_hash(), edited.content_hash());
+        assert_ne!(flat.content_hash(), Rope::from(&text[1..]).content_hash());
+        assert_eq!(Rope::from("").content_hash(), 0xcbf2_9ce4_8422_2325);
+        // a known FNV-1a test vector
+        assert_eq!(Rope::from("a").content_hash(), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn lsp_positions_astral_plane() {
+        // U+1F600 is one char but two UTF-16 code units
+        let rope = Rope::from("first\r\nab\u{1f600}cd\nlast");
+        let second = "first\r\n".len();
+        let c = second + "ab\u{1f600}".len();
+        assert_eq!(rope.lsp_position_of(c), (1, 4));
+        assert_eq!(rope.lsp_position_of(second + 2), (1, 2));
+        assert_eq!(rope.lsp_position_of(rope.len()), (2, 4));
+        assert_eq!(rope.offset_of_lsp_position(1, 4), c);
+        assert_eq!(rope.offset_of_lsp_position(1, 2), second + 2);
+        // inside the surrogate pair
+        assert_eq!(rope.offset_of_lsp_position(1, 3), c);
+        // past the end of the line, before its CRLF or LF
+        assert_eq!(rope.offset_of_lsp_position(0, 40), 5);
+        assert_eq!(rope.offset_of_lsp_position(1, 40), c + 2);
+        assert_eq!(rope.offset_of_lsp_position(2, 40), rope.len());
+        for offset in 0..=rope.len() {
+            if rope.is_codepoint_boundary(offset) {
+                let (line, col) = rope.lsp_position_of(offset);
+                if offset != 6 {
+                    assert_eq!(rope.offset_of_lsp_position(line, col), offset);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transform_lines_comments_range() {
+        let text = "fn main() {\n    let x = 1;\r\n\n    println!(\"{}\", x);\n}\n";
+        let rope = Rope::from(text);
+        let comment = |_: usize, line: &str| Some(format!("// {}", line));
+        let delta = rope.transform_lines(1..4, comment);
+        assert_eq!(
+            String::from(delta.apply(&rope)),
+            "fn main() {\n//     let x = 1;\r\n// \n//     println!(\"{}\", x);\n}\n"
+        );
+        // three replaced lines; the first and last lines and the line
+        // endings are copied
+        assert_eq!(delta.inserts_len(), 17 + 3 + 25);
+        assert_eq!(delta.summary(), (Interval::new(12, 51), 17 + 2 + 3 + 1 + 25));
+
+        // unchanged lines contribute nothing
+        let only_empty =
+            rope.transform_lines(
+                0..10,
+                |_, line| if line.is_empty() { Some("//".into()) } else { None },
+            );
+        assert_eq!(only_empty.inserts_len(), 2);
+        assert_eq!(only_empty.summary(), (Interval::new(28, 28), 2));
+        assert_eq!(String::from(only_empty.apply(&rope)), text.replace("\r\n\n", "\r\n//\n"));
+        assert!(rope.transform_lines(0..10, |_, line| Some(line.to_owned())).is_identity());
+        assert!(rope.transform_lines(7..9, |_, _| Some("x".into())).is_identity());
+
+        let numbers = rope.transform_lines(3..5, |i, _| Some(i.to_string()));
+        assert_eq!(String::from(numbers.apply(&rope)), "fn main() {\n    let x = 1;\r\n\n3\n4\n");
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn grapheme_cells_widths() {
+        let rope = Rope::from("a東e\u{301}\t\r\n");
+        let cells = rope.grapheme_cells(..);
+        let cell =
+            |start: usize, end: usize, width: usize| GraphemeCell { byte_range: start..end, width };
+        assert_eq!(
+            cells,
+            vec![cell(0, 1, 1), cell(1, 4, 2), cell(4, 7, 1), cell(7, 8, 0), cell(8, 10, 0)]
+        );
+        assert_eq!(rope.grapheme_cells(1..7), cells[1..3].to_vec());
+        assert_eq!(rope.grapheme_cells(4..5), vec![cell(4, 5, 1)]);
+        assert!(rope.grapheme_cells(4..4).is_empty());
+    }
+
+    #[test]
+    fn scan_chunks_from_finds_tab() {
+        let mut text = "no tabs on this line\n".repeat(150);
+        text.insert(1700, '\t');
+        text.insert(2900, '\t');
+        let rope = Rope::from(&text);
+        let first_tab = |cursor: &mut Cursor<RopeInfo, String>| {
+            cursor.scan_chunks_from(|chunk, base| memchr(b'\t', chunk.as_bytes()).map(|i| base + i))
+        };
+
+        for start in [0, 5, 1024, 1700, 1701, 2900, 2901, text.len()] {
+            let mut cursor = Cursor::new(&rope, start);
+            let expected = text[start..].find('\t').map(|i| start + i);
+            assert_eq!(first_tab(&mut cursor), expected, "from {}", start);
+            assert_eq!(cursor.pos(), expected.unwrap_or(text.len()));
+        }
+        assert!(rope.iter_chunks(..).count() > 2);
+    }
+
+    #[test]
+    fn rope_writer_matches_format() {
+        use std::fmt::Write;
+
+        let mut writer = RopeWriter::new();
+        let mut expected = String::new();
+        for i in 0..500 {
+            writeln!(writer, "line {} of {}: {:?}", i, 500, "ünïcode").unwrap();
+            expected.push_str(&format!("line {} of {}: {:?}\n", i, 500, "ünïcode"));
+        }
+        let rope = writer.into_rope();
+        assert!(expected.len() > 8 * MAX_LEAF);
+        assert_eq!(rope, Rope::from(&expected));
+    }
+
+    #[test]
+    fn rope_writer_bytes_split_characters() {
+        use std::io::Write;
+
+        let text = "a東\u{1f600}é\n".repeat(300);
+        let bytes = text.as_bytes();
+        for step in 1..=5 {
+            let mut writer = RopeWriter::new();
+            for piece in bytes.chunks(step) {
+                writer.write_all(piece).unwrap();
+            }
+            assert_eq!(writer.into_rope(), Rope::from(&text));
+        }
+
+        let mut writer = RopeWriter::new();
+        assert_eq!(writer.write(b"ok \xff").unwrap(), 3);
+        assert_eq!(writer.write(b"\xff").unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        writer.write_all(&[0xe6, 0x9d]).unwrap();
+        assert!(writer.write_all(b"x").is_err());
+        assert_eq!(String::from(writer.into_rope()), "ok \u{FFFD}");
+    }
+
+    #[test]
+    fn dedup_adjacent_lines() {
+        let cases = [
+            ("a\na\na\nb\nc\nc\na\n", "a\nb\nc\na\n"),
+            ("x\nx\r\ny\ny", "x\ny"),
+            ("a\na\na", "a"),
+            ("\n\n\nq\n", "\nq\n"),
+            ("same", "same"),
+            ("", ""),
+        ];
+        for &(text, expected) in &cases {
+            let rope = Rope::from(text);
+            let delta = rope.dedup_adjacent_lines();
+            assert_eq!(String::from(delta.apply(&rope)), expected, "{:?}", text);
+        }
+
+        // a single deletion per run, and nothing is inserted
+        let rope = Rope::from("keep\n".to_owned() + &"dup\n".repeat(500) + "keep\n");
+        let delta = rope.dedup_adjacent_lines();
+        assert_eq!(delta.inserts_len(), 0);
+        assert_eq!(delta.summary(), (Interval::new(9, 9 + 499 * 4), 0));
+    }
+
+    #[test]
+    fn prefix_lines_numbers() {
+        let rope = Rope::from("alpha\nbeta\r\n\ngamma");
+        let numbered = rope.prefix_lines(0..10, |n| format!("{}: ", n + 1));
+        assert_eq!(String::from(numbered.apply(&rope)), "1: alpha\n2: beta\r\n3: \n4: gamma");
+        assert_eq!(numbered.inserts_len(), 12);
+
+        let quoted = rope.prefix_lines(1..3, |_| "> ".to_owned());
+        assert_eq!(String::from(quoted.apply(&rope)), "alpha\n> beta\r\n> \ngamma");
+
+        // no prefix for the empty remainder after a final newline
+        let rope = Rope::from("one\ntwo\n");
+        let numbered = rope.prefix_lines(0..3, |n| format!("{}: ", n));
+        assert_eq!(String::from(numbered.apply(&rope)), "0: one\n1: two\n");
+        assert!(rope.prefix_lines(0..3, |_| String::new()).is_identity());
+        assert!(Rope::from("").prefix_lines(0..1, |_| "x".to_owned()).is_identity());
+    }
+
+    #[test]
+    fn line_intervals_tile() {
+        let text = "one\r\ntwo\n\nthree";
+        let rope = Rope::from(text);
+        let raw: Vec<_> = rope.line_intervals(.., true).collect();
+        let expected = vec![
+            (0, Interval::new(0, 5)),
+            (1, Interval::new(5, 9)),
+            (2, Interval::new(9, 10)),
+            (3, Interval::new(10, 15)),
+        ];
+        assert_eq!(raw, expected);
+        let ends: Vec<_> = raw.iter().map(|(_, iv)| iv.end).collect();
+        let starts: Vec<_> = raw.iter().skip(1).map(|(_, iv)| iv.start).collect();
+        assert_eq!(&ends[..3], &starts[..]);
+        assert_eq!(rope.len(), raw.last().unwrap().1.end);
+
+        let stripped: Vec<_> =
+            rope.line_intervals(.., false).map(|(_, iv)| &text[iv.start..iv.end]).collect();
+        assert_eq!(stripped, text.lines().collect::<Vec<_>>());
+
+        let partial: Vec<_> = rope.line_intervals(7..12, false).collect();
+        assert_eq!(
+            partial,
+            vec![(1, Interval::new(7, 8)), (2, Interval::new(9, 9)), (3, Interval::new(10, 12))]
+        );
+        assert_eq!(Rope::from("a\n").line_intervals(.., true).count(), 1);
+        assert_eq!(Rope::from("").line_intervals(.., true).count(), 0);
+    }
+
+    #[test]
+    fn line_intervals_across_leaves() {
+        let line = "0123456789abcdefghijklmnopqrstuvwxyz\r\n";
+        let text = line.repeat(200);
+        let rope = Rope::from(&text);
+        let mut count = 0;
+        for (i, iv) in rope.line_intervals(.., false) {
+            assert_eq!(i, count);
+            assert_eq!(iv, Interval::new(i * line.len(), i * line.len() + line.len() - 2));
+            count += 1;
+        }
+        assert_eq!(count, 200);
+    }
+
+    #[test]
+    fn keep_only_selection() {
+        let rope = Rope::from("hello brave new world");
+        let keep =
+            |start, end| String::from(rope.keep_only(Interval::new(start, end)).apply(&rope));
+        assert_eq!(keep(6, 15), "brave new");
+        assert_eq!(keep(0, 5), "hello");
+        assert_eq!(keep(16, 21), "world");
+        assert_eq!(keep(4, 4), "");
+        assert_eq!(keep(16, 100), "world");
+        assert!(rope.keep_only(Interval::new(0, rope.len())).is_identity());
+
+        let delta = rope.keep_only(Interval::new(6, 15));
+        assert_eq!(delta.inserts_len(), 0);
+        assert_eq!(delta.new_document_len(), 9);
+    }
+
+    #[test]
+    fn grapheme_at_zwj_sequence() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let text = format!("a{}e\u{301}\r\n", family);
+        let rope = Rope::from(text.as_str());
+        let fam_end = 1 + family.len();
+        let expected = Some((Interval::new(1, fam_end), family.to_owned()));
+        for offset in [1, 5, 8, fam_end - 4] {
+            assert_eq!(rope.grapheme_at(offset), expected, "offset {}", offset);
+        }
+        assert_eq!(rope.grapheme_at(0), Some((Interval::new(0, 1), "a".to_owned())));
+        let accented = Some((Interval::new(fam_end, fam_end + 3), "e\u{301}".to_owned()));
+        assert_eq!(rope.grapheme_at(fam_end + 1), accented);
+        let crlf = Some((Interval::new(fam_end + 3, text.len()), "\r\n".to_owned()));
+        assert_eq!(rope.grapheme_at(text.len() - 1), crlf);
+        assert_eq!(rope.grapheme_at(text.len()), None);
+        assert_eq!(Rope::from("").grapheme_at(0), None);
+    }
+
+    #[test]
+    fn ensure_final_newline() {
+        let empty = Rope::from("");
+        assert!(!empty.ends_with_newline());
+        assert!(empty.ensure_final_newline().is_none());
+
+        let rope = Rope::from("a");
+        assert!(!rope.ends_with_newline());
+        let delta = rope.ensure_final_newline().unwrap();
+        let fixed = delta.apply(&rope);
+        assert_eq!(String::from(&fixed), "a\n");
+        assert!(fixed.ends_with_newline());
+        assert!(fixed.ensure_final_newline().is_none());
+
+        assert!(Rope::from("a\r\n").ensure_final_newline().is_none());
+        let unterminated = Rope::from("x\n".repeat(1000) + "y");
+        let fixed = unterminated.ensure_final_newline().unwrap().apply(&unterminated);
+        assert_eq!(fixed.len(), unterminated.len() + 1);
+        assert!(fixed.ends_with_newline());
+    }
+
+    #[test]
+    fn range_eq_bytes_across_leaves() {
+        let text = format!("{}keyword{}", "a".repeat(MAX_LEAF - 3), "b".repeat(MAX_LEAF));
+        let rope = Rope::from(text.as_str());
+        let start = MAX_LEAF - 3;
+        let iv = Interval::new(start, start + 7);
+        assert!(rope.iter_chunks(iv).count() > 1);
+        assert!(rope.range_eq_bytes(iv, b"keyword"));
+        assert!(!rope.range_eq_bytes(iv, b"keywore"));
+        assert!(!rope.range_eq_bytes(iv, b"keywor"));
+        assert!(!rope.range_eq_bytes(Interval::new(start - 1, start + 6), b"keyword"));
+        assert!(rope.range_eq_bytes(Interval::new(3, 3), b""));
+        assert!(!rope.range_eq_bytes(Interval::new(text.len() - 1, text.len() + 1), b"bb"));
+    }
 }
 
 #[cfg(all(test, feature = "serde"))]
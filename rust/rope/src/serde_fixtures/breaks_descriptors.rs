@@ -201,27 +201,8 @@ fn build_breaks_tree(text_len: usize, offsets: &[usize]) -> Breaks {
     if text_len == 0 {
         return Breaks::new_no_break(0);
     }
-    if offsets.is_empty() {
-        let mut builder = BreakBuilder::new();
-        builder.add_no_break(text_len);
-        return builder.build();
-    }
     let mut builder = BreakBuilder::new();
-    let mut prev = 0;
-    for &offset in offsets {
-        let bounded = offset.min(text_len);
-        if bounded < prev {
-            continue;
-        }
-        let delta = bounded.saturating_sub(prev);
-        if delta > 0 {
-            builder.add_break(delta);
-            prev = bounded;
-        }
-    }
-    if prev < text_len {
-        builder.add_no_break(text_len - prev);
-    }
+    builder.extend_from_offsets(text_len, offsets);
     builder.build()
 }
 
@@ -16,6 +16,9 @@
 //! annotations. It is parameterized over a data type, so can be used for
 //! storing different annotations.
 
+use std::cmp::max;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
 use std::fmt;
 use std::marker::PhantomData;
 use std::mem;
@@ -113,6 +116,90 @@ pub struct SpansBuilder<T: Clone> {
     leaf: SpansLeaf<T>,
     len: usize,
     total_len: usize,
+    // Finished leaves, with their offsets, whose spans a policy span might
+    // still need to take back.
+    unsettled: Vec<(usize, Spans<T>)>,
+    // The number of spans at the start of the first unsettled leaf (or of
+    // `leaf`, if there is none) that no later span can change.
+    settled_in_leaf: usize,
+    // Spans taken back for a policy span, and those added after them, until
+    // none of them is left open.
+    held: HeldSpans<T>,
+}
+
+/// Spans in absolute coordinates, keyed by start and then by the order they
+/// were added, that can't yet go into a leaf.
+struct HeldSpans<T: Clone> {
+    // Spans that a later span might still overlap.
+    open: BTreeMap<(usize, u64), Span<T>>,
+    // Spans that no later span can overlap, waiting only for the open spans
+    // ordered before them.
+    closed: BTreeMap<(usize, u64), Span<T>>,
+    // The ends of the open spans, for moving them to `closed` as the start
+    // position passes them. Entries for spans no longer open are skipped.
+    ends: BinaryHeap<Reverse<(usize, usize, u64)>>,
+    next_seq: u64,
+}
+
+impl<T: Clone> HeldSpans<T> {
+    fn new() -> Self {
+        HeldSpans {
+            open: BTreeMap::new(),
+            closed: BTreeMap::new(),
+            ends: BinaryHeap::new(),
+            next_seq: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.open.is_empty() && self.closed.is_empty()
+    }
+
+    /// Adds a span, given that no span starting before `pos` will be added.
+    fn insert(&mut self, span: Span<T>, pos: usize) {
+        let key = (span.iv.start(), self.next_seq);
+        self.next_seq += 1;
+        if span.iv.end() > pos {
+            self.ends.push(Reverse((span.iv.end(), key.0, key.1)));
+            self.open.insert(key, span);
+        } else {
+            self.closed.insert(key, span);
+        }
+    }
+
+    /// Moves the open spans that no span starting at or after `pos` can
+    /// overlap to `closed`.
+    fn close_before(&mut self, pos: usize) {
+        while let Some(&Reverse((end, start, seq))) = self.ends.peek() {
+            if end > pos {
+                break;
+            }
+            self.ends.pop();
+            if let Some(span) = self.open.remove(&(start, seq)) {
+                self.closed.insert((start, seq), span);
+            }
+        }
+    }
+}
+
+/// How [`SpansBuilder::add_span_with_policy`] resolves a new span that
+/// overlaps spans already added.
+///
+/// [`SpansBuilder::add_span_with_policy`]: struct.SpansBuilder.html#method.add_span_with_policy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Don't add the new span.
+    Reject,
+    /// Add the new span whole, trimming the existing spans to the parts
+    /// outside it.
+    Replace,
+    /// Keep the existing spans, adding only the parts of the new span that
+    /// they don't cover.
+    Keep,
+    /// Split both the new and the existing spans at the edges of the overlap,
+    /// so each is divided into a part before it, the overlap itself, and a
+    /// part after it. The overlap is covered by a span with each payload.
+    Split,
 }
 
 impl<T: Clone> SpansBuilder<T> {
@@ -122,6 +209,9 @@ impl<T: Clone> SpansBuilder<T> {
             leaf: SpansLeaf::default(),
             len: 0,
             total_len,
+            unsettled: Vec::new(),
+            settled_in_leaf: 0,
+            held: HeldSpans::new(),
         }
     }
 
@@ -129,22 +219,190 @@ impl<T: Clone> SpansBuilder<T> {
     // Maybe take Span struct instead of separate iv, data args?
     pub fn add_span<IV: IntervalBounds>(&mut self, iv: IV, data: T) {
         let iv = iv.into_interval(self.total_len);
-        if self.leaf.spans.len() == MAX_LEAF {
-            let mut leaf = mem::take(&mut self.leaf);
-            leaf.len = iv.start() - self.len;
-            self.len = iv.start();
-            self.b.push(Node::from_leaf(leaf));
+        if self.held.is_empty() {
+            self.push_span(Span { iv, data });
+        } else {
+            self.held.close_before(iv.start());
+            self.held.insert(Span { iv, data }, iv.start());
+            self.flush_held();
         }
-        self.leaf.spans.push(Span { iv: iv.translate_neg(self.len), data })
+    }
+
+    /// Adds a span, resolving any overlap with spans already added according
+    /// to `policy`. Returns `false` if no part of the span was added.
+    ///
+    /// As with [`add_span`], spans must be added in nondecreasing start
+    /// order. Spans with no overlap are added unchanged, whatever the policy.
+    ///
+    /// [`add_span`]: #method.add_span
+    pub fn add_span_with_policy<IV: IntervalBounds>(
+        &mut self,
+        iv: IV,
+        data: T,
+        policy: OverlapPolicy,
+    ) -> bool {
+        let iv = iv.into_interval(self.total_len);
+        let pos = iv.start();
+        if self.held.is_empty() {
+            self.take_back_open(pos);
+        } else {
+            self.held.close_before(pos);
+        }
+        let overlapping: Vec<(usize, u64)> = self
+            .held
+            .open
+            .iter()
+            .filter(|(_, span)| !span.iv.intersect(iv).is_empty())
+            .map(|(&key, _)| key)
+            .collect();
+        if overlapping.is_empty() || iv.is_empty() {
+            self.held.insert(Span { iv, data }, pos);
+            self.flush_held();
+            return true;
+        }
+        let mut pieces = Vec::new();
+        match policy {
+            OverlapPolicy::Reject => {
+                self.flush_held();
+                return false;
+            }
+            OverlapPolicy::Keep => {
+                let mut rest = iv;
+                for key in &overlapping {
+                    let covered = self.held.open[key].iv;
+                    pieces.push(Span { iv: rest.prefix(covered), data: data.clone() });
+                    rest = rest.suffix(covered);
+                }
+                pieces.push(Span { iv: rest, data });
+            }
+            OverlapPolicy::Replace => {
+                for key in &overlapping {
+                    let existing = self.held.open.remove(key).unwrap();
+                    pieces.push(Span { iv: existing.iv.prefix(iv), data: existing.data.clone() });
+                    pieces.push(Span { iv: existing.iv.suffix(iv), data: existing.data });
+                }
+                pieces.push(Span { iv, data });
+            }
+            OverlapPolicy::Split => {
+                let mut cuts = Vec::new();
+                for key in &overlapping {
+                    let existing = self.held.open.remove(key).unwrap();
+                    let overlap = existing.iv.intersect(iv);
+                    cuts.extend_from_slice(&[overlap.start(), overlap.end()]);
+                    pieces.push(Span { iv: existing.iv.prefix(iv), data: existing.data.clone() });
+                    pieces.push(Span { iv: overlap, data: existing.data.clone() });
+                    pieces.push(Span { iv: existing.iv.suffix(iv), data: existing.data });
+                }
+                cuts.sort_unstable();
+                let mut start = iv.start();
+                for cut in cuts.into_iter().chain(Some(iv.end())) {
+                    pieces.push(Span { iv: Interval::new(start, cut), data: data.clone() });
+                    start = cut;
+                }
+            }
+        }
+        // only `Keep` can drop the whole of the new span
+        let added = policy != OverlapPolicy::Keep || pieces.iter().any(|p| !p.iv.is_empty());
+        for piece in pieces.into_iter().filter(|p| !p.iv.is_empty()) {
+            self.held.insert(piece, pos);
+        }
+        self.flush_held();
+        added
     }
 
     // Would make slightly more implementation sense to take total_len as an argument
     // here, but that's not quite the usual builder pattern.
     pub fn build(mut self) -> Spans<T> {
+        let mut held = mem::replace(&mut self.held, HeldSpans::new());
+        held.closed.append(&mut held.open);
+        for (_, span) in held.closed {
+            self.push_span(span);
+        }
+        self.settle();
         self.leaf.len = self.total_len - self.len;
         self.b.push(Node::from_leaf(self.leaf));
         self.b.build()
     }
+
+    /// Appends a span, in absolute coordinates, to the current leaf.
+    fn push_span(&mut self, Span { iv, data }: Span<T>) {
+        if self.leaf.spans.len() == MAX_LEAF {
+            let mut leaf = mem::take(&mut self.leaf);
+            leaf.len = iv.start() - self.len;
+            self.unsettled.push((self.len, Node::from_leaf(leaf)));
+            self.len = iv.start();
+        }
+        self.leaf.spans.push(Span { iv: iv.translate_neg(self.len), data })
+    }
+
+    /// Moves the unsettled leaves into the tree, and marks every span added
+    /// so far as settled.
+    fn settle(&mut self) {
+        for (_, node) in self.unsettled.drain(..) {
+            self.b.push(node);
+        }
+        self.settled_in_leaf = self.leaf.spans.len();
+    }
+
+    /// Moves the held spans that no open span precedes into the leaves.
+    fn flush_held(&mut self) {
+        loop {
+            let first_open = self.held.open.keys().next().copied();
+            match self.held.closed.keys().next() {
+                Some(&key) if first_open.map_or(true, |open| key < open) => (),
+                _ => break,
+            }
+            let (_, span) = self.held.closed.pop_first().unwrap();
+            self.push_span(span);
+        }
+        if self.held.is_empty() {
+            self.settle();
+        }
+    }
+
+    /// Takes back the unsettled spans from the first one still open at `pos`
+    /// onward into `held`, so that a policy span can change them.
+    fn take_back_open(&mut self, pos: usize) {
+        let n_unsettled = self.unsettled.len();
+        let mut first_open = None;
+        for i in 0..=n_unsettled {
+            let (offset, leaf) = if i < n_unsettled {
+                (self.unsettled[i].0, self.unsettled[i].1.get_leaf())
+            } else {
+                (self.len, &self.leaf)
+            };
+            let from = if i == 0 { self.settled_in_leaf } else { 0 };
+            if let Some(j) = leaf.spans[from..].iter().position(|span| span.iv.end() + offset > pos)
+            {
+                first_open = Some((i, from + j));
+                break;
+            }
+        }
+        let (i, j) = match first_open {
+            Some(found) => found,
+            None => return self.settle(),
+        };
+        let taken = self.unsettled.split_off(i);
+        for (_, node) in self.unsettled.drain(..) {
+            self.b.push(node);
+        }
+        let mut taken: Vec<(usize, SpansLeaf<T>)> =
+            taken.into_iter().map(|(offset, node)| (offset, node.get_leaf().clone())).collect();
+        taken.push((self.len, mem::take(&mut self.leaf)));
+        let (offset, mut leaf) = taken.remove(0);
+        let rest = leaf.spans.split_off(j);
+        self.leaf = leaf;
+        self.len = offset;
+        self.settled_in_leaf = j;
+        let spans = rest.into_iter().map(|span| (offset, span)).chain(
+            taken
+                .into_iter()
+                .flat_map(|(offset, leaf)| leaf.spans.into_iter().map(move |span| (offset, span))),
+        );
+        for (offset, Span { iv, data }) in spans {
+            self.held.insert(Span { iv: iv.translate(offset), data }, pos);
+        }
+    }
 }
 
 pub struct SpanIter<'a, T: 'a + Clone> {
@@ -184,6 +442,42 @@ impl<T: Clone> Spans<T> {
         builder.build()
     }
 
+    /// Returns the spans shifted for a single edit replacing the `deleted`
+    /// units at `edit_offset` with `inserted` new ones, without building a
+    /// delta.
+    ///
+    /// Span endpoints up to and including `edit_offset` are unchanged, and
+    /// those at or after the end of the deleted region move by
+    /// `inserted - deleted`. Endpoints strictly inside the deleted region are
+    /// clipped to the end of the inserted text, and spans left empty are
+    /// dropped. So a span overlapping the start of the deletion grows to cover
+    /// the inserted text, while one ending at `edit_offset` does not. This is
+    /// the same result as [`transform`](#method.transform) with the
+    /// equivalent delta.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the deleted region extends past the end of `self`.
+    pub fn adjust_for_edit(&self, edit_offset: usize, deleted: usize, inserted: usize) -> Spans<T> {
+        let deleted_end = edit_offset + deleted;
+        assert!(deleted_end <= self.len(), "edit extends past the end of the spans");
+        let adjust = |pos: usize| {
+            if pos <= edit_offset {
+                pos
+            } else {
+                max(pos, deleted_end) - deleted + inserted
+            }
+        };
+        let mut sb = SpansBuilder::new(self.len() - deleted + inserted);
+        for (iv, data) in self.iter() {
+            let (start, end) = (adjust(iv.start()), adjust(iv.end()));
+            if start < end {
+                sb.add_span(Interval::new(start, end), data.clone());
+            }
+        }
+        sb.build()
+    }
+
     /// Creates a new Spans instance by merging spans from `other` with `self`,
     /// using a closure to transform values.
     ///
@@ -298,6 +592,58 @@ impl<T: Clone> Spans<T> {
         SpanIter { cursor: Cursor::new(self, 0), ix: 0 }
     }
 
+    /// Returns the spans within `iv`, in a new `Spans` of length `iv.size()`
+    /// whose origin is `iv.start()`.
+    ///
+    /// Spans that partially overlap `iv` are clipped to the overlap, and
+    /// spans entirely outside it are dropped. The payloads of the retained
+    /// spans are cloned.
+    ///
+    /// Unlike `subseq`, which slices leaves and so clips each span to the
+    /// leaf that stores it, this preserves spans that extend across leaf
+    /// boundaries.
+    pub fn subseq_clipped(&self, iv: Interval) -> Spans<T> {
+        let iv = iv.intersect(Interval::new(0, self.len()));
+        let mut sb = SpansBuilder::new(iv.size());
+        for (span_iv, data) in self.iter() {
+            if span_iv.start() >= iv.end() {
+                break;
+            }
+            let clipped = span_iv.intersect(iv);
+            if !clipped.is_empty() {
+                sb.add_span(clipped.translate_neg(iv.start()), data.clone());
+            }
+        }
+        sb.build()
+    }
+
+    /// Returns the innermost span covering `offset`, with its interval.
+    ///
+    /// A span covers the offsets in its half-open interval, so a span ending
+    /// at `offset` does not count, nor does an empty span. When several spans
+    /// cover `offset`, the one with the smallest interval wins, and among
+    /// those of equal size, the first in iteration order.
+    ///
+    /// This descends only into subtrees whose spans reach `offset`, rather
+    /// than scanning every span before it.
+    pub fn span_at(&self, offset: usize) -> Option<(Interval, &T)> {
+        let mut best = None;
+        innermost_span_at(self, 0, offset, &mut best);
+        best
+    }
+
+    /// Returns a new `Spans` with the same intervals as `self`, whose
+    /// payloads are the result of applying `f` to each of the originals.
+    ///
+    /// This runs in time linear in the number of spans.
+    pub fn map<U: Clone, F: Fn(&T) -> U>(&self, f: F) -> Spans<U> {
+        let mut sb = SpansBuilder::new(self.len());
+        for (iv, data) in self.iter() {
+            sb.add_span(iv, f(data));
+        }
+        sb.build()
+    }
+
     /// Applies a generic delta to `self`, inserting empty spans for any
     /// added regions.
     ///
@@ -332,6 +678,38 @@ impl<T: Clone> Spans<T> {
     }
 }
 
+fn innermost_span_at<'a, T: Clone>(
+    node: &'a Spans<T>,
+    node_start: usize,
+    offset: usize,
+    best: &mut Option<(Interval, &'a T)>,
+) {
+    if !node.info().iv.translate(node_start).contains(offset) {
+        return;
+    }
+    if node.is_leaf() {
+        for span in &node.get_leaf().spans {
+            let iv = span.iv.translate(node_start);
+            if iv.start() > offset {
+                break;
+            }
+            if iv.contains(offset) && best.map_or(true, |(best_iv, _)| iv.size() < best_iv.size()) {
+                *best = Some((iv, &span.data));
+            }
+        }
+    } else {
+        let mut child_start = node_start;
+        for child in node.get_children() {
+            // spans start within the leaf that holds them
+            if child_start > offset {
+                break;
+            }
+            innermost_span_at(child, child_start, offset, best);
+            child_start += child.len();
+        }
+    }
+}
+
 impl<T: Clone + fmt::Debug> fmt::Debug for Spans<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let strs =
@@ -364,6 +742,7 @@ impl<'a, T: Clone> Iterator for SpanIter<'a, T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rope::Rope;
     #[test]
 
     fn test_merge() {
@@ -514,4 +893,208 @@ mod tests {
         spans.delete_after(Interval::new(5, 7));
         assert_eq!(spans.iter().count(), 1);
     }
+
+    #[test]
+    fn subseq_clipped_straddling() {
+        let mut sb = SpansBuilder::new(20);
+        sb.add_span(0..3, 'a');
+        sb.add_span(2..8, 'b');
+        sb.add_span(6..12, 'c');
+        sb.add_span(15..20, 'd');
+        let spans = sb.build();
+
+        let sub = spans.subseq_clipped(Interval::new(5, 10));
+        assert_eq!(sub.len(), 5);
+        let got: Vec<_> = sub.iter().map(|(iv, c)| (iv, *c)).collect();
+        assert_eq!(got, vec![(Interval::new(0, 3), 'b'), (Interval::new(1, 5), 'c')]);
+
+        assert_eq!(spans.subseq_clipped(Interval::new(12, 15)).iter().count(), 0);
+    }
+
+    #[test]
+    fn subseq_clipped_across_leaves() {
+        let mut sb = SpansBuilder::new(1000);
+        sb.add_span(0..500, 0);
+        for i in 1..200 {
+            sb.add_span(i * 5..i * 5 + 1, i);
+        }
+        let spans = sb.build();
+        assert!(spans.subseq(Interval::new(400, 600)).iter().next().unwrap().0.end() < 100);
+
+        let sub = spans.subseq_clipped(Interval::new(400, 600));
+        let (iv, val) = sub.iter().next().unwrap();
+        assert_eq!(iv, Interval::new(0, 100));
+        assert_eq!(*val, 0);
+        assert_eq!(sub.iter().count(), 41);
+    }
+
+    #[test]
+    fn map_payloads() {
+        let mut sb = SpansBuilder::new(30);
+        sb.add_span(0..4, 1u32);
+        sb.add_span(2..10, 2);
+        sb.add_span(20..30, 3);
+        let spans = sb.build();
+
+        let mapped = spans.map(|n| format!("#{}", n * 10));
+        assert_eq!(mapped.len(), spans.len());
+        let ivs: Vec<_> = spans.iter().map(|(iv, _)| iv).collect();
+        let mapped_ivs: Vec<_> = mapped.iter().map(|(iv, _)| iv).collect();
+        assert_eq!(ivs, mapped_ivs);
+        let payloads: Vec<_> = mapped.iter().map(|(_, s)| s.as_str()).collect();
+        assert_eq!(payloads, vec!["#10", "#20", "#30"]);
+    }
+
+    #[test]
+    fn add_span_with_policy() {
+        fn build(policy: OverlapPolicy) -> (bool, Vec<(Interval, char)>) {
+            let mut sb = SpansBuilder::new(30);
+            sb.add_span(0..10, 'a');
+            let added = sb.add_span_with_policy(5..15, 'b', policy);
+            sb.add_span_with_policy(20..25, 'c', policy);
+            let spans = sb.build();
+            (added, spans.iter().map(|(iv, c)| (iv, *c)).collect())
+        }
+        let iv = Interval::new;
+
+        assert_eq!(
+            build(OverlapPolicy::Reject),
+            (false, vec![(iv(0, 10), 'a'), (iv(20, 25), 'c')])
+        );
+        assert_eq!(
+            build(OverlapPolicy::Replace),
+            (true, vec![(iv(0, 5), 'a'), (iv(5, 15), 'b'), (iv(20, 25), 'c')])
+        );
+        assert_eq!(
+            build(OverlapPolicy::Keep),
+            (true, vec![(iv(0, 10), 'a'), (iv(10, 15), 'b'), (iv(20, 25), 'c')])
+        );
+        assert_eq!(
+            build(OverlapPolicy::Split),
+            (
+                true,
+                vec![
+                    (iv(0, 5), 'a'),
+                    (iv(5, 10), 'a'),
+                    (iv(5, 10), 'b'),
+                    (iv(10, 15), 'b'),
+                    (iv(20, 25), 'c')
+                ]
+            )
+        );
+
+        // a span inside another is split around it, or dropped entirely
+        let mut sb = SpansBuilder::new(30);
+        sb.add_span(0..20, 'a');
+        assert!(sb.add_span_with_policy(5..8, 'b', OverlapPolicy::Replace));
+        assert!(!sb.add_span_with_policy(10..12, 'c', OverlapPolicy::Keep));
+        let got: Vec<_> = sb.build().iter().map(|(iv, c)| (iv, *c)).collect();
+        assert_eq!(got, vec![(iv(0, 5), 'a'), (iv(5, 8), 'b'), (iv(8, 20), 'a')]);
+    }
+
+    #[test]
+    fn add_span_with_policy_across_leaves() {
+        let mut sb = SpansBuilder::new(1000);
+        sb.add_span(0..900, 0);
+        for i in 1..150 {
+            sb.add_span(i * 5..i * 5 + 2, i);
+        }
+        assert!(sb.add_span_with_policy(800..950, 999, OverlapPolicy::Keep));
+        let spans = sb.build();
+        assert_eq!(spans.iter().count(), 151);
+        assert_eq!(
+            spans.iter().last().map(|(iv, v)| (iv, *v)),
+            Some((Interval::new(900, 950), 999))
+        );
+    }
+
+    #[test]
+    fn add_span_after_policy_pieces() {
+        // the tail of `a` left by the replace starts after `c`
+        let mut sb = SpansBuilder::new(10);
+        sb.add_span(0..10, 'a');
+        sb.add_span_with_policy(2..4, 'b', OverlapPolicy::Replace);
+        sb.add_span(3..5, 'c');
+        let spans = sb.build();
+        let iv = Interval::new;
+        assert_eq!(
+            spans.iter().map(|(iv, c)| (iv, *c)).collect::<Vec<_>>(),
+            vec![(iv(0, 2), 'a'), (iv(2, 4), 'b'), (iv(3, 5), 'c'), (iv(4, 10), 'a')]
+        );
+    }
+
+    #[test]
+    fn span_at_innermost() {
+        let mut sb = SpansBuilder::new(1200);
+        sb.add_span(0..1000, "outer");
+        sb.add_span(10..40, "middle");
+        sb.add_span(12..15, "inner");
+        sb.add_span(12..15, "inner twin");
+        for i in 3..200 {
+            sb.add_span(5 * i..5 * i + 2, "small");
+        }
+        let spans = sb.build();
+        assert!(spans.iter().count() > MAX_LEAF);
+
+        assert_eq!(spans.span_at(13), Some((Interval::new(12, 15), &"inner")));
+        assert_eq!(spans.span_at(18), Some((Interval::new(10, 40), &"middle")));
+        assert_eq!(spans.span_at(15), Some((Interval::new(15, 17), &"small")));
+        assert_eq!(spans.span_at(502), Some((Interval::new(0, 1000), &"outer")));
+        assert_eq!(spans.span_at(1000), None);
+
+        // agrees with a linear scan everywhere
+        for offset in 0..1200 {
+            let mut expected: Option<(Interval, &&str)> = None;
+            for (iv, data) in spans.iter() {
+                if iv.contains(offset) && expected.map_or(true, |(e, _)| iv.size() < e.size()) {
+                    expected = Some((iv, data));
+                }
+            }
+            assert_eq!(spans.span_at(offset), expected, "at {}", offset);
+        }
+    }
+
+    #[test]
+    fn adjust_for_edit() {
+        let mut sb = SpansBuilder::new(30);
+        sb.add_span(0..4, 'a');
+        sb.add_span(6..12, 'b');
+        sb.add_span(12..14, 'c');
+        sb.add_span(20..25, 'd');
+        let spans = sb.build();
+
+        // replace 10..13 with 5 units
+        let adjusted = spans.adjust_for_edit(10, 3, 5);
+        assert_eq!(adjusted.len(), 32);
+        let result: Vec<_> = adjusted.iter().map(|(iv, c)| (iv, *c)).collect();
+        assert_eq!(
+            result,
+            vec![
+                (Interval::new(0, 4), 'a'),
+                (Interval::new(6, 15), 'b'),
+                (Interval::new(15, 16), 'c'),
+                (Interval::new(22, 27), 'd'),
+            ]
+        );
+
+        // deleting a span's whole extent drops it
+        let adjusted = spans.adjust_for_edit(12, 2, 0);
+        assert_eq!(adjusted.iter().filter(|(_, c)| **c == 'c').count(), 0);
+
+        // agrees with transforming by the equivalent delta
+        let base = Rope::from("x".repeat(30));
+        for &(offset, deleted, inserted) in
+            &[(10, 3, 5), (12, 0, 4), (4, 0, 2), (0, 30, 1), (5, 16, 0)]
+        {
+            let delta = Delta::simple_edit(
+                Interval::new(offset, offset + deleted),
+                Rope::from("y".repeat(inserted)),
+                base.len(),
+            );
+            let expected = spans.transform(0, spans.len(), &mut Transformer::new(&delta));
+            let adjusted = spans.adjust_for_edit(offset, deleted, inserted);
+            assert_eq!(adjusted.len(), expected.len());
+            assert!(adjusted.iter().eq(expected.iter()), "edit {:?}", (offset, deleted, inserted));
+        }
+    }
 }
@@ -12,10 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::compare::ne_idx;
 use crate::delta::{self, Delta};
 use crate::interval::Interval;
 use crate::multiset::{Subset, SubsetBuilder};
-use crate::rope::{Rope, RopeInfo};
+use crate::rope::{Rope, RopeDelta, RopeInfo};
 
 /// Creates a `Subset` of `s` by scanning through `substr` and finding which
 /// characters of `s` are missing from it in order. Returns a `Subset` which
@@ -34,18 +35,46 @@ pub fn find_deletions(substr: &str, s: &str) -> Subset {
     sb.build()
 }
 
+/// Applies `delta` to `base` and panics unless the result equals `expected`.
+///
+/// The panic message gives the first offset at which the result differs,
+/// with a little of the text on either side, which is far easier to read
+/// than the full strings when comparing large documents.
+pub fn assert_applies(delta: &RopeDelta, base: &Rope, expected: &Rope) {
+    let actual = String::from(delta.apply(base));
+    let expected = String::from(expected);
+    let diverge = match ne_idx(actual.as_bytes(), expected.as_bytes()) {
+        Some(ix) => ix,
+        None if actual.len() == expected.len() => return,
+        None => actual.len().min(expected.len()),
+    };
+    let context = |s: &str| {
+        let mut start = diverge.saturating_sub(20).min(s.len());
+        let mut end = (diverge + 20).min(s.len());
+        while !s.is_char_boundary(start) {
+            start -= 1;
+        }
+        while !s.is_char_boundary(end) {
+            end += 1;
+        }
+        s[start..end].to_owned()
+    };
+    panic!(
+        "delta result differs from expected at byte {} (lengths {} and {}):\n  actual:   {:?}\n  expected: {:?}",
+        diverge,
+        actual.len(),
+        expected.len(),
+        context(&actual),
+        context(&expected),
+    );
+}
+
 impl Delta<RopeInfo, String> {
     pub fn apply_to_string(&self, s: &str) -> String {
         String::from(self.apply(&Rope::from(s)))
     }
 }
 
-impl PartialEq for Rope {
-    fn eq(&self, other: &Rope) -> bool {
-        String::from(self) == String::from(other)
-    }
-}
-
 pub fn parse_subset(s: &str) -> Subset {
     let mut sb = SubsetBuilder::new();
 
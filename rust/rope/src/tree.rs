@@ -21,6 +21,7 @@ use std::sync::Arc;
 
 use smallvec::SmallVec;
 
+use crate::delta::{Delta, Transformer};
 use crate::interval::{Interval, IntervalBounds};
 
 const MIN_CHILDREN: usize = 4;
@@ -274,6 +275,69 @@ impl<N: NodeInfo<L>, L: Leaf> SharedNode<N, L> {
     }
 }
 
+impl<N: NodeInfo<L> + PartialEq, L: Leaf> Node<N, L> {
+    /// Checks the structural invariants of the tree, returning a description
+    /// of the first violation found.
+    ///
+    /// Every internal node must have between `MIN_CHILDREN` and
+    /// `MAX_CHILDREN` children (the root may have fewer than the minimum, but
+    /// at least two), all of one height less than its own; every leaf other
+    /// than the root must satisfy `is_ok_child`; and the cached length and
+    /// info of every node must match those recomputed from its contents.
+    ///
+    /// This walks the whole tree and is intended as an oracle for tests and
+    /// fuzzing.
+    pub fn validate_invariants(&self) -> Result<(), String> {
+        self.validate_node(true, 0)
+    }
+
+    fn validate_node(&self, is_root: bool, offset: usize) -> Result<(), String> {
+        let body = self.body();
+        let (len, info) = match body.val {
+            NodeVal::Leaf(ref l) => {
+                if !is_root && !l.is_ok_child() {
+                    return Err(format!("undersized leaf of length {} at {}", l.len(), offset));
+                }
+                (l.len(), N::compute_info(l))
+            }
+            NodeVal::Internal(ref children) => {
+                let min_children = if is_root { 2 } else { MIN_CHILDREN };
+                if children.len() < min_children || children.len() > MAX_CHILDREN {
+                    return Err(format!(
+                        "node at {} of height {} has {} children",
+                        offset,
+                        body.height,
+                        children.len()
+                    ));
+                }
+                let mut len = 0;
+                let mut info = N::identity();
+                for child in children {
+                    if child.height() + 1 != body.height {
+                        return Err(format!(
+                            "child at {} has height {} under a node of height {}",
+                            offset + len,
+                            child.height(),
+                            body.height
+                        ));
+                    }
+                    child.validate_node(false, offset + len)?;
+                    len += child.len();
+                    info.accumulate(&child.body().info);
+                }
+                (len, info)
+            }
+        };
+        if len != body.len {
+            return Err(format!("node at {} caches length {} but has {}", offset, body.len, len));
+        }
+        if info != body.info {
+            return Err(format!("node at {} caches stale info", offset));
+        }
+        Ok(())
+    }
+}
+
 // also consider making Metric a newtype for usize, so type system can
 // help separate metrics
 
@@ -352,6 +416,12 @@ impl<N: NodeInfo<L>, L: Leaf> Node<N, L> {
         self.shared.body()
     }
 
+    /// The accumulated info for the whole tree.
+    #[inline]
+    pub(crate) fn info(&self) -> &N {
+        &self.body().info
+    }
+
     pub fn from_leaf(l: L) -> Node<N, L> {
         let len = l.len();
         let info = N::compute_info(&l);
@@ -388,7 +458,7 @@ impl<N: NodeInfo<L>, L: Leaf> Node<N, L> {
         self.body().height
     }
 
-    fn is_leaf(&self) -> bool {
+    pub(crate) fn is_leaf(&self) -> bool {
         self.height() == 0
     }
 
@@ -396,7 +466,7 @@ impl<N: NodeInfo<L>, L: Leaf> Node<N, L> {
         self.body().info.interval(self.body().len)
     }
 
-    fn get_children(&self) -> &[Node<N, L>] {
+    pub(crate) fn get_children(&self) -> &[Node<N, L>] {
         if let NodeVal::Internal(ref v) = self.body().val {
             v
         } else {
@@ -404,7 +474,7 @@ impl<N: NodeInfo<L>, L: Leaf> Node<N, L> {
         }
     }
 
-    fn get_leaf(&self) -> &L {
+    pub(crate) fn get_leaf(&self) -> &L {
         if let NodeVal::Leaf(ref l) = self.body().val {
             l
         } else {
@@ -518,6 +588,19 @@ impl<N: NodeInfo<L>, L: Leaf> Node<N, L> {
         }
     }
 
+    /// Concatenates `nodes` in order into a single tree.
+    ///
+    /// All nodes are pushed into one [`TreeBuilder`], which keeps the result
+    /// balanced; folding [`concat`](#method.concat) over many small nodes can
+    /// produce a needlessly deep tree.
+    pub fn concat_many(nodes: Vec<Node<N, L>>) -> Node<N, L> {
+        let mut b = TreeBuilder::new();
+        for node in nodes {
+            b.push(node);
+        }
+        b.build()
+    }
+
     pub fn measure<M: Metric<N, L>>(&self) -> usize {
         M::measure(&self.body().info, self.body().len)
     }
@@ -606,6 +689,58 @@ impl<N: DefaultMetricProvider<L>, L: Leaf> Node<N, L> {
     pub fn count_base_units<M: Metric<N, L>>(&self, offset: usize) -> usize {
         N::convert_to_default::<M>(self, offset)
     }
+
+    /// Returns the smallest offset (in the default metric) at which
+    /// `count::<M>` reaches `target`, or the length of the tree if `target`
+    /// exceeds its measure.
+    ///
+    /// This is a single descent through the tree, not a binary search over
+    /// `count`.
+    ///
+    /// Time complexity: O(log n)
+    pub fn lower_bound_by_measure<M: Metric<N, L>>(&self, target: usize) -> usize {
+        if target == 0 {
+            0
+        } else if target > self.measure::<M>() {
+            self.len()
+        } else {
+            self.count_base_units::<M>(target)
+        }
+    }
+
+    /// Measures the text within `range` (given in the default metric) using
+    /// another metric.
+    ///
+    /// This is equivalent to `count::<M>(range.end) - count::<M>(range.start)`,
+    /// but only descends once through the nodes shared by both endpoints.
+    /// The end of the range is clamped to the length of the tree.
+    pub fn measure_range<M: Metric<N, L>>(&self, range: Interval) -> usize {
+        let mut start = range.start;
+        let mut end = range.end.min(self.len());
+        if start >= end {
+            return 0;
+        }
+        let mut node = self;
+        'descend: while node.height() > 0 {
+            let mut offset = 0;
+            for child in node.get_children() {
+                let child_end = offset + child.len();
+                if start < child_end {
+                    if end > child_end {
+                        // the range straddles several children
+                        break 'descend;
+                    }
+                    node = child;
+                    start -= offset;
+                    end -= offset;
+                    continue 'descend;
+                }
+                offset = child_end;
+            }
+            unreachable!("range start is within the node");
+        }
+        node.count::<M>(end) - node.count::<M>(start)
+    }
 }
 
 impl<N: NodeInfo<L>, L: Leaf> Default for Node<N, L> {
@@ -753,6 +888,14 @@ impl<N: NodeInfo<L>, L: Leaf> TreeBuilder<N, L> {
                             }
                             #[cfg(feature = "tree_builder_slice_trace")]
                             self.trace_extend_frame(node_height, node_len, node_ptr, false);
+                            if self.stack.last().unwrap().len() == MAX_CHILDREN {
+                                n = self.pop();
+                                #[cfg(feature = "tree_builder_slice_trace")]
+                                {
+                                    reuse_flag = false;
+                                }
+                                continue;
+                            }
                         }
                         break;
                     } else {
@@ -951,6 +1094,25 @@ impl<N: NodeInfo<L>, L: Leaf> TreeBuilder<N, L> {
         }
     }
 
+    /// Pushes a sequence of slices, each given as a tree and an interval
+    /// within it, as by [`push_slice`] on each in order.
+    ///
+    /// Slices too small to stand alone as leaves are merged into the
+    /// preceding leaf, even when it came from a different tree, so
+    /// assembling a rope from many small fragments does not leave a trail of
+    /// tiny leaves.
+    ///
+    /// [`push_slice`]: #method.push_slice
+    pub fn push_slices<'a>(&mut self, parts: impl IntoIterator<Item = (&'a Node<N, L>, Interval)>)
+    where
+        N: 'a,
+        L: 'a,
+    {
+        for (n, iv) in parts {
+            self.push_slice(n, iv);
+        }
+    }
+
     /// Append a sequence of leaves.
     pub fn push_leaves(&mut self, leaves: impl IntoIterator<Item = L>) {
         for leaf in leaves.into_iter() {
@@ -963,6 +1125,25 @@ impl<N: NodeInfo<L>, L: Leaf> TreeBuilder<N, L> {
         self.push(Node::from_leaf(l))
     }
 
+    /// Append a single leaf as a child of its own, never merging it into the
+    /// previous leaf. The caller is responsible for the leaf being a valid
+    /// child if the tree ends up with more than one leaf.
+    pub(crate) fn push_leaf_unmerged(&mut self, l: L) {
+        let n = Node::from_leaf(l);
+        #[cfg(feature = "tree_builder_slice_trace")]
+        let (node_ptr, node_len) = (Self::node_identity(&n), n.len());
+        match self.stack.last_mut() {
+            Some(frame) if frame[0].height() == 0 => frame.push(n),
+            _ => self.stack.push(vec![n]),
+        }
+        #[cfg(feature = "tree_builder_slice_trace")]
+        self.trace_extend_frame(0, node_len, node_ptr, false);
+        if self.stack.last().unwrap().len() == MAX_CHILDREN {
+            let full = self.pop();
+            self.push(full);
+        }
+    }
+
     /// Append a slice of a single leaf.
     pub fn push_leaf_slice(&mut self, l: &L, iv: Interval) {
         let node = Node::from_leaf(l.subseq(iv));
@@ -1119,6 +1300,16 @@ impl<N: NodeInfo<L>, L: Leaf> CursorDescriptor<N, L> {
             None
         }
     }
+
+    /// Maps the captured position through `delta`, returning the matching
+    /// position in the edited tree.
+    ///
+    /// The cached path is not updated, so the descriptor itself stays stale;
+    /// create a new [`Cursor`] at the returned position instead. Text inserted
+    /// exactly at the position is placed after it.
+    pub fn remap_position(&self, delta: &Delta<N, L>) -> usize {
+        Transformer::new(delta).transform(self.position, false)
+    }
 }
 
 #[cfg(feature = "cursor_state")]
@@ -1207,6 +1398,16 @@ impl<'a, N: NodeInfo<L>, L: Leaf> Cursor<'a, N, L> {
         self.leaf.map(|l| (l, self.position - self.offset_of_leaf))
     }
 
+    /// Returns the address of the current leaf, if the cursor is valid.
+    ///
+    /// Leaves are stored in shared, copy-on-write nodes, so two cursors
+    /// return the same pointer exactly when their leaves share one
+    /// allocation. This is intended for tests that check which parts of a
+    /// tree an operation left untouched.
+    pub fn leaf_ptr(&self) -> Option<*const ()> {
+        self.leaf.map(|l| l as *const L as *const ())
+    }
+
     /// Set the position of the cursor.
     ///
     /// The cursor is valid after this call.
@@ -1231,6 +1432,18 @@ impl<'a, N: NodeInfo<L>, L: Leaf> Cursor<'a, N, L> {
         self.position
     }
 
+    /// Returns the signed distance in base units from this cursor to `other`,
+    /// positive if `other` is further along.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the two cursors are not on the same tree, as determined by
+    /// pointer identity of their roots.
+    pub fn distance_to(&self, other: &Cursor<'_, N, L>) -> isize {
+        assert!(self.root.ptr_eq(other.root), "distance_to called on cursors from different trees");
+        other.position as isize - self.position as isize
+    }
+
     /// Creates a [`CursorDescriptor`] snapshot of the current cursor state.
     ///
     /// The descriptor owns all cached path information, allowing the cursor to
@@ -1320,24 +1533,73 @@ impl<'a, N: NodeInfo<L>, L: Leaf> Cursor<'a, N, L> {
     ///
     /// Note: the beginning and end of the tree may or may not be boundaries, depending on the
     /// metric. If the metric is not `can_fragment`, then they always are.
-    pub fn is_boundary<M: Metric<N, L>>(&mut self) -> bool {
+    pub fn is_boundary<M: Metric<N, L>>(&self) -> bool {
         if self.leaf.is_none() {
             // not at a valid position
             return false;
         }
-        if self.position == self.offset_of_leaf && !M::can_fragment() {
+        self.is_boundary_at::<M>(self.position)
+    }
+
+    /// Determine whether `pos` is a boundary, without moving the cursor.
+    ///
+    /// This agrees with [`is_boundary`](#method.is_boundary) after a call to
+    /// `set(pos)`. Positions past the end of the tree are never boundaries.
+    ///
+    /// Time complexity: O(1) when `pos` falls within the current leaf,
+    /// O(log n) otherwise.
+    pub fn is_boundary_at<M: Metric<N, L>>(&self, pos: usize) -> bool {
+        if pos > self.root.len() {
+            return false;
+        }
+        let (leaf, offset_of_leaf) = match self.leaf {
+            Some(leaf) if pos > self.offset_of_leaf && pos <= self.offset_of_leaf + leaf.len() => {
+                (leaf, self.offset_of_leaf)
+            }
+            _ => self.leaf_before(pos),
+        };
+        let offset_in_leaf = pos - offset_of_leaf;
+        // interior leaf edges are always boundaries for metrics that can't fragment
+        let at_leaf_edge =
+            offset_in_leaf == 0 || (offset_in_leaf == leaf.len() && pos < self.root.len());
+        if at_leaf_edge && !M::can_fragment() {
             return true;
         }
-        if self.position == 0 || self.position > self.offset_of_leaf {
-            return M::is_boundary(self.leaf.unwrap(), self.position - self.offset_of_leaf);
+        M::is_boundary(leaf, offset_in_leaf)
+    }
+
+    /// Returns the measure of the tree from its start up to the cursor's
+    /// current position.
+    ///
+    /// This is the same as `root.count::<M>(cursor.pos())`, but only the
+    /// prefix of the current leaf is scanned.
+    ///
+    /// Time complexity: O(log n) plus a scan of the current leaf.
+    pub fn measure<M: Metric<N, L>>(&self) -> usize {
+        let (leaf, offset_of_leaf) = match self.leaf {
+            Some(leaf) => (leaf, self.offset_of_leaf),
+            None => self.leaf_before(self.position.min(self.root.len())),
+        };
+        self.measure_leaf::<M>(offset_of_leaf)
+            + M::from_base_units(leaf, self.position.min(self.root.len()) - offset_of_leaf)
+    }
+
+    /// Finds the leaf containing the base unit just before `pos` (the first leaf
+    /// when `pos` is 0), returning it along with its offset. Does not move the cursor.
+    fn leaf_before(&self, pos: usize) -> (&'a L, usize) {
+        let target = pos.saturating_sub(1);
+        let mut node = self.root;
+        let mut offset = 0;
+        while node.height() > 0 {
+            let children = node.get_children();
+            let mut i = 0;
+            while i + 1 < children.len() && offset + children[i].len() <= target {
+                offset += children[i].len();
+                i += 1;
+            }
+            node = &children[i];
         }
-        // tricky case, at beginning of leaf, need to query end of previous
-        // leaf; TODO: would be nice if we could do it another way that didn't
-        // make the method &mut self.
-        let l = self.prev_leaf().unwrap().0;
-        let result = M::is_boundary(l, l.len());
-        let _ = self.next_leaf();
-        result
+        (node.get_leaf(), offset)
     }
 
     /// Moves the cursor to the previous boundary.
@@ -1862,6 +2124,7 @@ fn clone_node_arc<N: NodeInfo<L>, L: Leaf>(node: &Node<N, L>) -> Arc<NodeBody<N,
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::helpers::string_leaf::MIN_LEAF;
     use crate::rope::*;
 
     fn build_triangle(n: u32) -> String {
@@ -2066,6 +2329,72 @@ mod test {
         assert_eq!(None, cursor.prev::<LinesMetric>());
     }
 
+    #[test]
+    fn is_boundary_at_parity() {
+        let s = build_triangle(120).replace("aaa", "a\u{e9}a");
+        let text = Rope::from(&s);
+        assert!(text.height() > 0);
+        let mut leaf_edges = Vec::new();
+        let mut cursor = Cursor::new(&text, 0);
+        while let Some((_, _)) = cursor.get_leaf() {
+            leaf_edges.push(cursor.pos());
+            cursor.next_leaf();
+        }
+        assert!(leaf_edges.len() > 1);
+
+        let parked = Cursor::new(&text, text.len() / 2);
+        for pos in 0..=text.len() {
+            let cursor = Cursor::new(&text, pos);
+            let is_line = pos > 0 && s.as_bytes()[pos - 1] == b'\n';
+            let is_codepoint = s.is_char_boundary(pos);
+            assert_eq!(cursor.is_boundary::<LinesMetric>(), is_line, "pos {}", pos);
+            assert_eq!(cursor.is_boundary::<BaseMetric>(), is_codepoint, "pos {}", pos);
+            assert_eq!(parked.is_boundary_at::<LinesMetric>(pos), is_line, "pos {}", pos);
+            assert_eq!(parked.is_boundary_at::<BaseMetric>(pos), is_codepoint, "pos {}", pos);
+            // querying must not move the cursor
+            assert_eq!(parked.pos(), text.len() / 2);
+        }
+        for &edge in &leaf_edges {
+            let cursor = Cursor::new(&text, edge);
+            assert_eq!(cursor.get_leaf().unwrap().1, 0);
+            assert_eq!(
+                cursor.is_boundary::<BaseMetric>(),
+                parked.is_boundary_at::<BaseMetric>(edge)
+            );
+            assert_eq!(
+                cursor.is_boundary::<LinesMetric>(),
+                parked.is_boundary_at::<LinesMetric>(edge)
+            );
+        }
+        assert!(!parked.is_boundary_at::<BaseMetric>(text.len() + 1));
+    }
+
+    #[test]
+    fn measure_range_parity() {
+        let text = Rope::from(build_triangle(200).replace("aa", "\u{1f600}a"));
+        assert!(text.height() > 1);
+        let len = text.len();
+        let points: Vec<usize> = (0..=len)
+            .step_by(97)
+            .chain(vec![1, len - 1, len])
+            .filter(|&p| text.is_codepoint_boundary(p))
+            .collect();
+        for &a in &points {
+            for &b in points.iter().filter(|&&b| b >= a) {
+                let iv = Interval::new(a, b);
+                let lines = text.count::<LinesMetric>(b) - text.count::<LinesMetric>(a);
+                let utf16 =
+                    text.count::<Utf16CodeUnitsMetric>(b) - text.count::<Utf16CodeUnitsMetric>(a);
+                assert_eq!(text.measure_range::<LinesMetric>(iv), lines, "{:?}", iv);
+                assert_eq!(text.measure_range::<Utf16CodeUnitsMetric>(iv), utf16, "{:?}", iv);
+            }
+        }
+        assert_eq!(
+            text.measure_range::<LinesMetric>(Interval::new(0, len + 10)),
+            text.measure::<LinesMetric>()
+        );
+    }
+
     #[test]
     fn balance_invariant() {
         let mut tb = TreeBuilder::<RopeInfo, String>::new();
@@ -2074,4 +2403,190 @@ mod test {
         let tree = tb.build();
         println!("height {}", tree.height());
     }
+
+    #[test]
+    fn push_leaf_split_fills_frame() {
+        fn check_children(node: &Rope) {
+            if !node.is_leaf() {
+                let children = node.get_children();
+                assert!(children.len() <= MAX_CHILDREN, "node with {} children", children.len());
+                children.iter().for_each(check_children);
+            }
+        }
+        // the short leaf is merged into the last full one, splitting off a
+        // new leaf that brings the frame to MAX_CHILDREN
+        let mut tb = TreeBuilder::<RopeInfo, String>::new();
+        for _ in 0..MAX_CHILDREN - 1 {
+            tb.push_leaf("a".repeat(1000));
+        }
+        tb.push_leaf("b".repeat(100));
+        tb.push_leaf("c".repeat(1000));
+        let tree = tb.build();
+        check_children(&tree);
+        assert_eq!(tree.len(), MAX_CHILDREN * 1000 + 100);
+    }
+
+    #[test]
+    fn cursor_measure() {
+        let text = Rope::from(build_triangle(50));
+        for pos in 0..=text.len() {
+            let cursor = Cursor::new(&text, pos);
+            assert_eq!(cursor.measure::<LinesMetric>(), text.count::<LinesMetric>(pos), "{}", pos);
+        }
+        let mut cursor = Cursor::new(&text, text.len());
+        assert_eq!(cursor.next::<LinesMetric>(), None);
+        assert_eq!(cursor.measure::<LinesMetric>(), text.measure::<LinesMetric>());
+    }
+
+    #[test]
+    fn descriptor_remap_position() {
+        use crate::delta::Delta;
+
+        let text = Rope::from("hello world");
+        let before = Cursor::new(&text, 2).to_descriptor();
+        let at = Cursor::new(&text, 5).to_descriptor();
+        let after = Cursor::new(&text, 8).to_descriptor();
+
+        let delta = Delta::simple_edit(Interval::new(5, 5), Rope::from(", big"), text.len());
+        let edited = delta.apply(&text);
+        assert_eq!(String::from(&edited), "hello, big world");
+
+        assert_eq!(before.remap_position(&delta), 2);
+        assert_eq!(at.remap_position(&delta), 5);
+        let pos = after.remap_position(&delta);
+        assert_eq!(pos, 13);
+        assert_eq!(Cursor::new(&edited, pos).peek_next_codepoint(), Some('r'));
+    }
+
+    /// Builds a node without any of the usual checks, to corrupt on purpose.
+    fn raw_node(len: usize, info: RopeInfo, val: NodeVal<RopeInfo, String>) -> Rope {
+        let height = match val {
+            NodeVal::Leaf(_) => 0,
+            NodeVal::Internal(ref children) => children[0].height() + 1,
+        };
+        Node::from_shared(SharedNode::new(NodeBody { height, len, info, val }))
+    }
+
+    #[test]
+    fn validate_invariants() {
+        let text = build_triangle(200);
+        let mut rope = Rope::from(&text);
+        assert_eq!(rope.validate_invariants(), Ok(()));
+        rope.edit(100..5000, "replacement\n".repeat(300));
+        assert_eq!(rope.validate_invariants(), Ok(()));
+        let rope = Rope::concat(rope.subseq(7..3000), Rope::from("x"));
+        assert_eq!(rope.validate_invariants(), Ok(()));
+        assert_eq!(Rope::from("").validate_invariants(), Ok(()));
+
+        let leaf = |s: &str| {
+            raw_node(s.len(), RopeInfo::compute_info(&s.to_owned()), NodeVal::Leaf(s.to_owned()))
+        };
+        let big = "a\n".repeat(MIN_LEAF);
+
+        let wrong_len = raw_node(3, RopeInfo::compute_info(&big), NodeVal::Leaf(big.clone()));
+        assert!(wrong_len.validate_invariants().unwrap_err().contains("length"));
+
+        let stale = raw_node(big.len(), RopeInfo::identity(), NodeVal::Leaf(big.clone()));
+        assert!(stale.validate_invariants().unwrap_err().contains("stale info"));
+
+        let lone_child =
+            raw_node(big.len(), RopeInfo::compute_info(&big), NodeVal::Internal(vec![leaf(&big)]));
+        assert!(lone_child.validate_invariants().unwrap_err().contains("1 children"));
+
+        let children = vec![leaf(&big), leaf("tiny")];
+        let info = RopeInfo::compute_info(&(big.clone() + "tiny"));
+        let small_leaf = raw_node(big.len() + 4, info, NodeVal::Internal(children));
+        assert!(small_leaf.validate_invariants().unwrap_err().contains("undersized leaf"));
+    }
+
+    #[test]
+    fn cursor_distance_to() {
+        let rope = Rope::from("a".repeat(5000));
+        let a = Cursor::new(&rope, 100);
+        let b = Cursor::new(&rope, 4200);
+        assert_eq!(a.distance_to(&b), 4100);
+        assert_eq!(b.distance_to(&a), -4100);
+        assert_eq!(a.distance_to(&a), 0);
+
+        let clone = rope.clone();
+        assert_eq!(Cursor::new(&clone, 0).distance_to(&b), 4200);
+    }
+
+    #[test]
+    #[should_panic(expected = "different trees")]
+    fn cursor_distance_to_other_tree() {
+        let one = Rope::from("abc");
+        let two = Rope::from("abc");
+        Cursor::new(&one, 0).distance_to(&Cursor::new(&two, 1));
+    }
+
+    #[test]
+    fn leaf_ptr_shared_after_edit() {
+        fn leaf_ptrs(rope: &Rope) -> Vec<*const ()> {
+            let mut cursor = Cursor::new(rope, 0);
+            let mut ptrs = vec![cursor.leaf_ptr().unwrap()];
+            while cursor.next_leaf().is_some() {
+                ptrs.push(cursor.leaf_ptr().unwrap());
+            }
+            ptrs
+        }
+
+        let mut b = TreeBuilder::new();
+        for c in ["a", "b", "c", "d", "e"] {
+            b.push_leaf(c.repeat(2 * MIN_LEAF + 2));
+        }
+        let before: Rope = b.build();
+        let mut after = before.clone();
+        // both halves of the edited leaf stay big enough to stand alone
+        let mid = 2 * (2 * MIN_LEAF + 2) + MIN_LEAF + 1;
+        after.edit(mid..mid + 1, "X");
+
+        let (old, new) = (leaf_ptrs(&before), leaf_ptrs(&after));
+        assert_eq!(old.len(), 5);
+        // the edited leaf is rebuilt (as two leaves), the rest are shared
+        assert_eq!(new.len(), 6);
+        assert_eq!(old[..2], new[..2]);
+        assert_eq!(old[3..], new[4..]);
+        assert!(!new.contains(&old[2]));
+    }
+
+    #[test]
+    fn push_slices_from_several_ropes() {
+        let a = Rope::from("the quick brown fox");
+        let b = Rope::from("x".repeat(6 * MIN_LEAF));
+        let c = Rope::from("jumps over the lazy dog");
+        let parts =
+            [(&a, Interval::new(4, 10)), (&c, Interval::new(6, 15)), (&a, Interval::new(16, 19))];
+
+        let mut builder = TreeBuilder::new();
+        builder.push_slices(parts.iter().cloned());
+        let small: Rope = builder.build();
+        assert_eq!(String::from(&small), "quick over the fox");
+        // the fragments share a single leaf
+        assert_eq!(small.height(), 0);
+
+        let mut builder = TreeBuilder::new();
+        builder.push_slices(vec![
+            (&a, Interval::new(0, 4)),
+            (&b, Interval::new(5, 2500)),
+            (&c, Interval::new(0, 5)),
+        ]);
+        let big: Rope = builder.build();
+        let expected = format!("the {}jumps", "x".repeat(2495));
+        assert_eq!(String::from(&big), expected);
+        assert_eq!(big, Rope::from(expected.as_str()));
+    }
+
+    #[test]
+    fn concat_many_matches_left_fold() {
+        let pieces: Vec<Rope> = (0..10_000).map(|i| Rope::from(format!("{:05},", i))).collect();
+        let folded = pieces.iter().cloned().fold(Rope::default(), Node::concat);
+        let many = Node::concat_many(pieces);
+        assert_eq!(many.len(), 60_000);
+        assert_eq!(String::from(&many), String::from(&folded));
+        assert!(many.height() <= folded.height());
+        assert!(many.height() <= 3, "height {}", many.height());
+
+        assert_eq!(Node::concat_many(Vec::<Rope>::new()).len(), 0);
+    }
 }